@@ -35,16 +35,18 @@ if [ -n "$TZ" ]; then
 fi
 
 echo "[entrypoint] APP_ENV=$APP_ENV"
-echo "[entrypoint] Starting Redis..."
-redis-server --daemonize yes
+if [ "$SCHEDULE_STORE" = "redis" ] && [ -z "$CELERY_BROKER_URL" ]; then
+    echo "[entrypoint] Starting Redis..."
+    redis-server --daemonize yes
 
-echo "[entrypoint] Waiting for Redis to be ready..."
-until redis-cli ping >/dev/null 2>&1; do
-    echo "[entrypoint] Redis not ready, sleeping 1s..."
-    sleep 1
-done
+    echo "[entrypoint] Waiting for Redis to be ready..."
+    until redis-cli ping >/dev/null 2>&1; do
+        echo "[entrypoint] Redis not ready, sleeping 1s..."
+        sleep 1
+    done
 
-echo "[entrypoint] Redis is ready"
+    echo "[entrypoint] Redis is ready"
+fi
 
 
 if [ "$APP_ENV" = "production" ]; then
@@ -0,0 +1,77 @@
+use crate::cli::find_database;
+use crate::core::context::Context;
+use crate::services::backup::BackupService;
+use crate::settings::CONFIG;
+use crate::utils::common::BackupMethod;
+use crate::utils::edge_key::decode_edge_key;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+use uuid::Uuid;
+
+/// `app backup <generated_id> [--output <path>]`: back up a database now.
+/// The result goes through the outbox like any manual backup, unless `output`
+/// asks for the unencrypted dump to be written locally instead.
+pub async fn run(generated_id: &str, output: Option<&str>) -> i32 {
+    let db = match find_database(generated_id).await {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    if output.is_none()
+        && let Err(e) = decode_edge_key(&CONFIG.edge_key)
+    {
+        eprintln!("Cannot upload without a valid EDGE_KEY ({}), use --output", e);
+        return 1;
+    }
+
+    let temp_dir = match TempDir::new() {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Failed to create temp dir: {}", e);
+            return 1;
+        }
+    };
+
+    let result = match BackupService::run(db, temp_dir.path()).await {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("Backup of {} failed: {:#}", generated_id, e);
+            return 1;
+        }
+    };
+    let Some(file) = result.backup_file.clone() else {
+        eprintln!("Backup of {} {}", generated_id, result.status);
+        return 1;
+    };
+
+    match output {
+        Some(output) => {
+            let mut target = Path::new(output).to_path_buf();
+            if target.is_dir()
+                && let Some(name) = file.file_name()
+            {
+                target = target.join(name);
+            }
+            if let Err(e) = std::fs::copy(&file, &target) {
+                eprintln!("Failed to write {}: {}", target.display(), e);
+                return 1;
+            }
+            println!("Backup of {} written to {}", generated_id, target.display());
+        }
+        None => {
+            let service = BackupService::new(Arc::new(Context::new()));
+            let job_id = Uuid::new_v4().to_string();
+            service
+                .send_result(result, BackupMethod::Manual, &job_id)
+                .await;
+            println!(
+                "Backup of {} queued for upload as job {}, see the logs for its delivery",
+                generated_id, job_id
+            );
+        }
+    }
+    0
+}
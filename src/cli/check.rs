@@ -0,0 +1,144 @@
+use crate::cli::Report;
+use crate::domain::factory::DatabaseFactory;
+use crate::domain::{mongodb, postgres};
+use crate::services::config::{ConfigService, DatabaseConfig, DbType};
+use crate::services::{credentials, discovery};
+use crate::settings::CONFIG;
+use crate::utils::edge_key::decode_edge_key;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// `app check`: validate the config, the EDGE_KEY, the server and every
+/// database, and return the process exit code
+pub async fn run() -> i32 {
+    let mut report = Report::default();
+
+    report.section("Config");
+    let path = ConfigService::path();
+    let config = match ConfigService::load(None) {
+        Ok(config) => {
+            report.ok(format!(
+                "{}: {} database(s), {} server(s)",
+                path,
+                config.databases.len(),
+                config.servers.len()
+            ));
+            Some(config)
+        }
+        Err(e) => {
+            report.fail(format!("{}: {}", path, e));
+            None
+        }
+    };
+
+    report.section("EDGE_KEY");
+    match decode_edge_key(&CONFIG.edge_key) {
+        Ok(key) => {
+            report.ok(format!("agent {} on {}", key.agent_id, key.server_url));
+
+            report.section("Server");
+            let client = reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default();
+            match client.get(&key.server_url).send().await {
+                Ok(resp) if !resp.status().is_server_error() => {
+                    report.ok(format!("{} reachable ({})", key.server_url, resp.status()))
+                }
+                Ok(resp) => report.fail(format!("{} answered {}", key.server_url, resp.status())),
+                Err(e) => report.fail(format!("{} unreachable: {}", key.server_url, e)),
+            }
+        }
+        Err(_) if CONFIG.edge_key.is_empty() => report.fail("EDGE_KEY missing"),
+        Err(e) => report.fail(format!("invalid: {}", e)),
+    }
+
+    let Some(config) = config else {
+        return report.finish();
+    };
+
+    let mut databases = config.databases.clone();
+    if !config.servers.is_empty() {
+        report.section("Servers");
+        for server in config.servers.iter() {
+            match discovery::discover(server).await {
+                Ok(found) => {
+                    report.ok(format!("{}: {} database(s) selected", server.name, found.len()));
+                    databases.extend(found);
+                }
+                Err(e) => report.fail(format!("{}: {:#}", server.name, e)),
+            }
+        }
+    }
+
+    report.section("Databases");
+    for db in databases {
+        check_database(&mut report, db).await;
+    }
+
+    report.finish()
+}
+
+async fn check_database(report: &mut Report, db: DatabaseConfig) {
+    let label = format!("{} ({})", db.name, db.generated_id);
+    let (db, _lease) = match credentials::resolve(db).await {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            report.fail(format!("{}: {:#}", label, e));
+            return;
+        }
+    };
+
+    let instance = DatabaseFactory::create_for_backup(db.clone()).await;
+    match instance.ping().await {
+        Ok(true) => report.ok(format!("{}: reachable", label)),
+        Ok(false) => {
+            report.fail(format!("{}: not reachable", label));
+            return;
+        }
+        Err(e) => {
+            report.fail(format!("{}: not reachable: {:#}", label, e));
+            return;
+        }
+    }
+
+    let (dir, binaries): (Option<PathBuf>, &[&str]) = match db.db_type {
+        DbType::Postgresql => match postgres::server_version(&db).await {
+            Ok(version) => (Some(postgres::select_pg_path(&version)), &["pg_dump", "pg_restore"]),
+            Err(e) => {
+                report.fail(format!("{}: cannot read server version: {:#}", label, e));
+                return;
+            }
+        },
+        DbType::Mysql | DbType::Mariadb => (None, &["mysqldump", "mysql"]),
+        DbType::MongoDB => (Some(mongodb::select_mongo_path()), &["mongodump", "mongorestore"]),
+    };
+
+    let missing: Vec<_> = binaries
+        .iter()
+        .filter(|name| match &dir {
+            Some(dir) => !dir.join(name).is_file(),
+            None => !in_path(name),
+        })
+        .collect();
+    let location = dir
+        .as_ref()
+        .map_or("PATH".to_string(), |d| d.display().to_string());
+    if missing.is_empty() {
+        report.ok(format!("{}: {} found in {}", label, binaries.join(", "), location));
+    } else {
+        report.fail(format!(
+            "{}: {} missing from {}",
+            label,
+            missing.iter().map(|s| **s).collect::<Vec<_>>().join(", "),
+            location
+        ));
+    }
+}
+
+fn in_path(name: &str) -> bool {
+    env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).any(|dir| Path::new(&dir).join(name).is_file()))
+        .unwrap_or(false)
+}
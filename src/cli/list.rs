@@ -0,0 +1,39 @@
+use crate::cli::databases;
+use crate::domain::factory::DatabaseFactory;
+use crate::services::credentials;
+
+/// `app list`: configured and discovered databases with their reachability
+pub async fn run() -> i32 {
+    let databases = match databases().await {
+        Ok(databases) => databases,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+
+    println!(
+        "{:<36}  {:<10}  {:<9}  {:<30}  NAME",
+        "GENERATED ID", "TYPE", "REACHABLE", "HOST"
+    );
+    for db in databases {
+        let reachable = match credentials::resolve(db.clone()).await {
+            Ok((resolved, _lease)) => DatabaseFactory::create_for_backup(resolved)
+                .await
+                .ping()
+                .await
+                .unwrap_or(false),
+            Err(_) => false,
+        };
+        println!(
+            "{:<36}  {:<10}  {:<9}  {:<30}  {}{}",
+            db.generated_id,
+            db.db_type.as_str(),
+            if reachable { "yes" } else { "no" },
+            format!("{}:{}", db.host, db.port),
+            db.name,
+            if db.sandbox { " (sandbox)" } else { "" }
+        );
+    }
+    0
+}
@@ -0,0 +1,114 @@
+pub mod backup;
+pub mod check;
+pub mod list;
+pub mod restore;
+pub mod schedules;
+
+use crate::services::config::{ConfigService, DatabaseConfig};
+use crate::services::discovery;
+use crate::utils::logging;
+
+const USAGE: &str = "\
+Usage: app [COMMAND]
+
+Without a command, runs the agent.
+
+Commands:
+  check                                  Validate the config, EDGE_KEY, server and databases
+  backup <generated_id> [--output <path>] Back up a database now and upload it, or write the
+                                         unencrypted dump to a local path
+  restore <generated_id> --file <path|url> Restore a database from a dump
+  list                                   Configured databases and their reachability
+  schedules                              Scheduled tasks with their next run";
+
+/// Run the subcommand in `args`, returning its exit code, or `None` when
+/// there is none and the agent should start
+pub async fn run(args: &[String]) -> Option<i32> {
+    let (command, rest) = args.split_first()?;
+    let code = match (command.as_str(), rest) {
+        ("check", []) => check::run().await,
+        ("list", []) => list::run().await,
+        ("schedules", []) => schedules::run().await,
+        ("backup", [id]) => {
+            logging::init_logger();
+            backup::run(id, None).await
+        }
+        ("backup", [id, flag, output]) if flag == "--output" => {
+            logging::init_logger();
+            backup::run(id, Some(output)).await
+        }
+        ("restore", [id, flag, file]) if flag == "--file" => {
+            logging::init_logger();
+            restore::run(id, file).await
+        }
+        ("help" | "--help" | "-h", _) => {
+            println!("{}", USAGE);
+            0
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            2
+        }
+    };
+    Some(code)
+}
+
+/// Every database the agent handles: listed ones, then those discovered on
+/// servers. Discovery errors go to stderr, `list` and `schedules` have no logger.
+pub async fn databases() -> Result<Vec<DatabaseConfig>, String> {
+    let config = ConfigService::load(None)?;
+    let mut databases = config.databases.clone();
+    for server in config.servers.iter() {
+        match discovery::discover(server).await {
+            Ok(found) => databases.extend(
+                found
+                    .into_iter()
+                    .filter(|db| config.find(&db.generated_id).is_none()),
+            ),
+            Err(e) => eprintln!("Failed to discover databases on {}: {:#}", server.name, e),
+        }
+    }
+    Ok(databases)
+}
+
+/// Database with this `generated_id`
+pub async fn find_database(generated_id: &str) -> Result<DatabaseConfig, String> {
+    databases()
+        .await?
+        .into_iter()
+        .find(|db| db.generated_id == generated_id)
+        .ok_or_else(|| format!("Unknown database {}", generated_id))
+}
+
+/// Outcome lines of a command, counted to pick the exit code
+#[derive(Default)]
+pub struct Report {
+    failures: usize,
+}
+
+impl Report {
+    pub fn section(&self, title: &str) {
+        println!("{}", title);
+    }
+
+    pub fn ok(&self, message: impl AsRef<str>) {
+        println!("  [ok]   {}", message.as_ref());
+    }
+
+    pub fn fail(&mut self, message: impl AsRef<str>) {
+        self.failures += 1;
+        println!("  [FAIL] {}", message.as_ref());
+    }
+
+    /// Process exit code, non-zero when anything failed
+    pub fn finish(&self) -> i32 {
+        println!();
+        if self.failures == 0 {
+            println!("All checks passed");
+            0
+        } else {
+            println!("{} check(s) failed", self.failures);
+            1
+        }
+    }
+}
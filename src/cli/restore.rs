@@ -0,0 +1,64 @@
+use crate::cli::find_database;
+use crate::domain::factory::RestoreScope;
+use crate::services::restore::RestoreService;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// `app restore <generated_id> --file <path|url>`: restore a database now.
+/// Nothing is reported to the server, which did not request this restore.
+pub async fn run(generated_id: &str, file: &str) -> i32 {
+    let local = Path::new(file).is_file();
+    if !local && !file.starts_with("http://") && !file.starts_with("https://") {
+        if file.contains("://") {
+            eprintln!("Unsupported URL {}, only http(s) URLs are downloaded", file);
+        } else {
+            eprintln!("No such file {}", file);
+        }
+        return 1;
+    }
+
+    let db = match find_database(generated_id).await {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+
+    let temp_dir = match TempDir::new() {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Failed to create temp dir: {}", e);
+            return 1;
+        }
+    };
+
+    // Local files are read here, the service itself only downloads. Whether
+    // the dump is complete is unknown, so nothing it leaves out is dropped.
+    let result = if local {
+        match tokio::fs::read(file).await {
+            Ok(bytes) => RestoreService::restore(db, temp_dir.path(), bytes, RestoreScope::Partial).await,
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", file, e);
+                return 1;
+            }
+        }
+    } else {
+        RestoreService::run(db, temp_dir.path(), file, RestoreScope::Partial).await
+    };
+
+    match result {
+        Ok(result) if result.status == "success" => {
+            println!("Restore of {} from {} succeeded", generated_id, file);
+            0
+        }
+        Ok(result) => {
+            eprintln!("Restore of {} {}, see the logs", generated_id, result.status);
+            1
+        }
+        Err(e) => {
+            eprintln!("Restore of {} failed: {:#}", generated_id, e);
+            1
+        }
+    }
+}
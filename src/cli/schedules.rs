@@ -0,0 +1,41 @@
+use crate::utils::task_manager::store::schedule_store;
+use chrono::{DateTime, Local};
+
+fn format_time(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S %Z").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+/// `app schedules`: tasks of the schedule store with their next run
+pub async fn run() -> i32 {
+    let store = schedule_store().await;
+    let mut tasks = match store.list().await {
+        Ok(tasks) => tasks,
+        Err(e) => {
+            eprintln!("Failed to read the schedule: {:#}", e);
+            return 1;
+        }
+    };
+    tasks.sort_by_key(|(_, _, next_run)| next_run.unwrap_or(i64::MAX));
+
+    println!(
+        "{:<56}  {:<18}  {:<16}  {:<26}  ERROR",
+        "NAME", "CRON", "TIMEZONE", "NEXT RUN"
+    );
+    for (name, task, next_run) in tasks {
+        let next = match next_run {
+            Some(at) if task.enabled => format_time(at),
+            _ => "-".to_string(),
+        };
+        println!(
+            "{:<56}  {:<18}  {:<16}  {:<26}  {}",
+            name,
+            task.cron,
+            task.timezone.as_deref().unwrap_or("local"),
+            next,
+            task.last_error.as_deref().unwrap_or("")
+        );
+    }
+    0
+}
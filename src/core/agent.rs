@@ -1,13 +1,17 @@
 #![allow(dead_code)]
 
 use crate::core::context::Context;
+use crate::core::executor::Executor;
 use crate::services::backup::BackupService;
-use crate::services::config::ConfigService;
+use crate::services::config::{BlackoutAction, ConfigService};
 use crate::services::cron::CronService;
 use crate::services::status::StatusService;
+use crate::utils::blackout::ActiveBlackout;
 use crate::utils::common::BackupMethod;
+use chrono::Utc;
+use std::collections::HashMap;
 use std::sync::Arc;
-use tracing::info;
+use tracing::{info, warn};
 use crate::services::restore::RestoreService;
 
 pub struct Agent {
@@ -17,6 +21,9 @@ pub struct Agent {
     cron_service: CronService,
     backup_service: BackupService,
     restore_service: RestoreService,
+    /// Databases whose requested backup was reported as held by a blackout,
+    /// so that it is reported once per window
+    held_backups: HashMap<String, ActiveBlackout>,
 }
 
 impl Agent {
@@ -35,24 +42,72 @@ impl Agent {
             cron_service,
             backup_service,
             restore_service,
+            held_backups: HashMap::new(),
         }
     }
 
     pub async fn run(&mut self, method: BackupMethod) -> Result<(), Box<dyn std::error::Error>> {
-        let config = self.config_service.load(None)?;
-        let ping_result = self.status_service.ping(&config.databases).await?;
+        let (config, discovered) = self.config_service.discover().await?;
+        for id in discovered.added.iter() {
+            info!("Discovered database {} added", id);
+        }
+        for id in discovered.removed.iter() {
+            info!("Discovered database {} no longer present", id);
+            self.cron_service.remove(id).await;
+        }
+        let managed: Vec<_> = config.managed().cloned().collect();
+        let schedule_errors = self.cron_service.quarantined().await;
+        let ping_result = self
+            .status_service
+            .ping(&managed, &schedule_errors)
+            .await?;
+
+        for job_id in ping_result.cancel_jobs.iter() {
+            if !Executor::cancel(job_id) {
+                warn!("Cannot cancel job {}: not queued nor running", job_id);
+            }
+        }
+
+        for db in managed.iter() {
+            let _ = self.cron_service.sync_verification(db).await;
+        }
 
         for db in ping_result.databases.iter() {
             info!(
                 "Generated Id: {} | backup action: {} | restore action: {}",
                 db.generated_id, db.data.backup.action, db.data.restore.action
             );
-            let _ = self.cron_service.sync(db).await;
+            let misfire = config
+                .find(&db.generated_id)
+                .map(|c| c.misfire)
+                .unwrap_or_default();
+            let _ = self.cron_service.sync(db, misfire).await;
+
+            if !db.data.backup.action {
+                self.held_backups.remove(&db.generated_id);
+            }
 
             if db.data.backup.action {
+                let held = self.held_backups.get(&db.generated_id);
+                match config.blackout(&db.generated_id, Utc::now().timestamp()) {
+                    Some(blackout) => {
+                        if held != Some(&blackout) {
+                            self.backup_service
+                                .defer(&db.generated_id, method, blackout)
+                                .await;
+                            self.held_backups.insert(db.generated_id.clone(), blackout);
+                        }
+                        continue;
+                    }
+                    // A skipped request stays dropped until the server clears it
+                    None if held.is_some_and(|b| b.action == BlackoutAction::Skip) => continue,
+                    None => {
+                        self.held_backups.remove(&db.generated_id);
+                    }
+                }
                 let _ = self
                     .backup_service
-                    .dispatch(&db.generated_id, &config, method.clone())
+                    .dispatch(&db.generated_id, &config, method)
                     .await;
             } else if db.data.restore.action {
                 let _ = self
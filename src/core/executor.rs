@@ -0,0 +1,219 @@
+use crate::settings::CONFIG;
+use crate::utils::common::BackupMethod;
+use crate::utils::process::JOB_CANCEL;
+use futures::future::BoxFuture;
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+/// Jobs of a lower priority wait until no job of a higher one can start
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Manual,
+    Automatic,
+}
+
+impl From<BackupMethod> for Priority {
+    fn from(method: BackupMethod) -> Self {
+        match method {
+            BackupMethod::Manual => Priority::Manual,
+            BackupMethod::Automatic => Priority::Automatic,
+        }
+    }
+}
+
+/// Unit of work against one database host
+pub struct Job {
+    pub id: String,
+    pub host: String,
+    pub priority: Priority,
+    pub label: String,
+    /// Operation and database, at most one such job is queued or running
+    key: (&'static str, String),
+    cancel: Arc<AtomicBool>,
+    future: BoxFuture<'static, ()>,
+    /// Report sent instead of running the job when it is cancelled while queued
+    on_cancel: Option<BoxFuture<'static, ()>>,
+}
+
+impl Job {
+    /// `kind` operation on `generated_id`, `run` receives the job id and
+    /// builds the work to execute
+    pub fn new<F, Fut>(
+        host: &str,
+        priority: Priority,
+        kind: &'static str,
+        generated_id: &str,
+        run: F,
+    ) -> Self
+    where
+        F: FnOnce(String) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let id = Uuid::new_v4().to_string();
+        Job {
+            future: Box::pin(run(id.clone())),
+            id,
+            host: host.to_string(),
+            priority,
+            label: format!("{} {}", kind, generated_id),
+            key: (kind, generated_id.to_string()),
+            cancel: Arc::new(AtomicBool::new(false)),
+            on_cancel: None,
+        }
+    }
+
+    /// `report` receives the job id and reports the job as cancelled, when
+    /// it is cancelled before it started
+    pub fn on_cancel<F, Fut>(mut self, report: F) -> Self
+    where
+        F: FnOnce(String) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_cancel = Some(Box::pin(report(self.id.clone())));
+        self
+    }
+}
+
+pub enum Submitted {
+    Started,
+    /// Waiting for a free slot
+    Queued,
+    /// Not run, the same operation on the database is queued or running
+    Duplicate,
+}
+
+#[derive(Default)]
+struct State {
+    /// Waiting jobs by priority, then submission order
+    queue: BTreeMap<(Priority, u64), Job>,
+    seq: u64,
+    running: usize,
+    per_host: HashMap<String, usize>,
+    /// Cancellation flags of running jobs by id
+    cancel: HashMap<String, Arc<AtomicBool>>,
+    /// Keys of queued and running jobs
+    active: HashSet<(&'static str, String)>,
+}
+
+static STATE: Lazy<Mutex<State>> = Lazy::new(|| Mutex::new(State::default()));
+
+/// Frees the job's slots when it ends, even if it panicked
+struct Slot {
+    id: String,
+    host: String,
+    key: (&'static str, String),
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let mut state = STATE.lock().unwrap();
+        state.running -= 1;
+        state.cancel.remove(&self.id);
+        state.active.remove(&self.key);
+        if let Some(count) = state.per_host.get_mut(&self.host) {
+            *count -= 1;
+            if *count == 0 {
+                state.per_host.remove(&self.host);
+            }
+        }
+        Executor::pump(&mut state);
+    }
+}
+
+/// Runs jobs within `MAX_CONCURRENT_JOBS` overall and `MAX_JOBS_PER_HOST` per
+/// database host, in FIFO order within each priority
+pub struct Executor;
+
+impl Executor {
+    pub fn submit(job: Job) -> Submitted {
+        let mut state = STATE.lock().unwrap();
+        if !state.active.insert(job.key.clone()) {
+            info!("Job {} skipped: already queued or running", job.label);
+            return Submitted::Duplicate;
+        }
+
+        let id = job.id.clone();
+        let key = (job.priority, state.seq);
+        state.seq += 1;
+
+        debug!("Job {} ({}) submitted", id, job.label);
+        state.queue.insert(key, job);
+        Self::pump(&mut state);
+
+        match state.queue.values().position(|j| j.id == id) {
+            Some(position) => {
+                info!(
+                    "Job {} queued at position {} ({} running)",
+                    id, position, state.running
+                );
+                Submitted::Queued
+            }
+            None => Submitted::Started,
+        }
+    }
+
+    /// Start queued jobs while slots are free. A job whose host is busy does
+    /// not hold back jobs for other hosts.
+    fn pump(state: &mut State) {
+        while state.running < CONFIG.max_concurrent_jobs {
+            let next = state.queue.iter().find_map(|(key, job)| {
+                let busy = state.per_host.get(&job.host).copied().unwrap_or(0);
+                (busy < CONFIG.max_jobs_per_host).then_some(*key)
+            });
+            let Some(key) = next else {
+                break;
+            };
+            let Some(job) = state.queue.remove(&key) else {
+                break;
+            };
+
+            state.running += 1;
+            *state.per_host.entry(job.host.clone()).or_default() += 1;
+            state.cancel.insert(job.id.clone(), job.cancel.clone());
+            info!("Job {} ({}) started", job.id, job.label);
+
+            let slot = Slot {
+                id: job.id,
+                host: job.host,
+                key: job.key,
+            };
+            let future = JOB_CANCEL.scope(job.cancel, job.future);
+            tokio::spawn(async move {
+                let _slot = slot;
+                future.await;
+            });
+        }
+    }
+
+    /// Drop a queued job, or kill the processes of a running one so that it
+    /// finishes as `cancelled`. Returns false for unknown or finished jobs.
+    pub fn cancel(id: &str) -> bool {
+        let mut state = STATE.lock().unwrap();
+        if let Some(flag) = state.cancel.get(id) {
+            flag.store(true, Ordering::SeqCst);
+            info!("Job {} cancellation requested", id);
+            return true;
+        }
+
+        let queued = state
+            .queue
+            .iter()
+            .find_map(|(key, job)| (job.id == id).then_some(*key));
+        match queued.and_then(|key| state.queue.remove(&key)) {
+            Some(job) => {
+                state.active.remove(&job.key);
+                info!("Job {} ({}) cancelled before it started", id, job.label);
+                if let Some(report) = job.on_cancel {
+                    tokio::spawn(report);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
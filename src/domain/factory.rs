@@ -1,18 +1,46 @@
 use crate::domain::mongodb::database::MongoDatabase;
 use crate::domain::mysql::database::MySQLDatabase;
 use crate::domain::postgres::database::PostgresDatabase;
-use crate::domain::postgres::{detect_format_from_file, detect_format_from_size};
+use crate::domain::postgres::{
+    PostgresDumpFormat, detect_format_from_file, detect_format_from_size,
+};
 use crate::services::config::{DatabaseConfig, DbType};
+use crate::services::options::PostgresFormat;
 use anyhow::Result;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+/// Row (or document) count per table (or collection), keyed by qualified name
+pub type Inventory = BTreeMap<String, u64>;
+
+/// What a restore does with the objects of the database missing from the dump
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreScope {
+    /// The dump holds the whole database, which is dropped and recreated
+    Full,
+    /// The dump may hold part of the database only: its objects are replaced
+    /// and everything else is kept
+    Partial,
+}
+
+impl RestoreScope {
+    /// Scope of a dump recorded as `filtered` at backup time, partial when unknown
+    pub fn of(filtered: Option<bool>) -> Self {
+        match filtered {
+            Some(false) => RestoreScope::Full,
+            _ => RestoreScope::Partial,
+        }
+    }
+}
+
 #[async_trait::async_trait]
 pub trait Database: Send + Sync {
     fn file_extension(&self) -> &'static str;
     async fn ping(&self) -> Result<bool>;
     async fn backup(&self, backup_dir: &Path) -> Result<PathBuf>;
-    async fn restore(&self, restore_file: &Path) -> Result<()>;
+    async fn restore(&self, restore_file: &Path, scope: RestoreScope) -> Result<()>;
+    async fn inventory(&self) -> Result<Inventory>;
 }
 
 pub struct DatabaseFactory;
@@ -21,7 +49,11 @@ impl DatabaseFactory {
     pub async fn create_for_backup(cfg: DatabaseConfig) -> Arc<dyn Database> {
         match cfg.db_type {
             DbType::Postgresql => {
-                let format = detect_format_from_size(&cfg).await;
+                let format = match cfg.postgres_options().format {
+                    PostgresFormat::Auto => detect_format_from_size(&cfg).await,
+                    PostgresFormat::Custom => PostgresDumpFormat::Fc,
+                    PostgresFormat::Directory => PostgresDumpFormat::Fd,
+                };
                 Arc::new(PostgresDatabase::new(cfg, format))
             }
             DbType::Mysql => Arc::new(MySQLDatabase::new(cfg)),
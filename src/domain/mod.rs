@@ -1,5 +1,5 @@
 pub mod factory;
 pub mod postgres;
 pub mod mysql;
-mod mongodb;
+pub mod mongodb;
 
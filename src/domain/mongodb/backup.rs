@@ -1,5 +1,6 @@
-use crate::domain::mongodb::connection::{get_mongo_uri, select_mongo_path};
+use crate::domain::mongodb::connection::{select_mongo_path, tool_args};
 use crate::services::config::DatabaseConfig;
+use crate::utils::process::ProcessControl;
 use anyhow::{Context, Result};
 use std::path::PathBuf;
 use std::process::Command;
@@ -10,18 +11,30 @@ pub async fn run(
     backup_dir: PathBuf,
     file_extension: &'static str,
 ) -> Result<PathBuf> {
+    let control = ProcessControl::current(cfg.backup_timeout());
     tokio::task::spawn_blocking(move || -> Result<PathBuf> {
         debug!("Starting MongoDB backup for database {}", cfg.name);
 
         let file_path = backup_dir.join(format!("{}{}", cfg.generated_id, file_extension));
         let mongodump = select_mongo_path().join("mongodump");
-        let uri = get_mongo_uri(cfg.clone());
+        let (connection, password) = tool_args(&cfg);
+        let options = cfg.mongo_options();
 
-        let output = Command::new(mongodump)
-            .arg(format!("--uri={}", uri))
-            .arg(format!("--archive={}", file_path.display()))
-            .arg("--gzip")
-            .output()
+        let output = control
+            .output_with_input(
+                Command::new(mongodump)
+                    .args(&connection)
+                    .arg(format!("--archive={}", file_path.display()))
+                    .arg("--gzip")
+                    .args(
+                        options
+                            .exclude_collections
+                            .iter()
+                            .map(|c| format!("--excludeCollection={}", c)),
+                    )
+                    .args(&options.extra_args.backup),
+                password.unwrap_or_default().as_bytes(),
+            )
             .context("MongoDB backup failed")?;
 
         if !output.status.success() {
@@ -1,16 +1,58 @@
 use crate::services::config::DatabaseConfig;
+use crate::services::options::{TlsMode, TlsOptions};
+use crate::utils::process::within;
 use anyhow::Result;
 use mongodb::Client;
+use mongodb::options::Tls;
+use std::time::Duration;
 
 pub async fn connect(cfg: DatabaseConfig) -> Result<Client> {
+    let mongo = cfg.mongo_options();
     let uri = get_mongo_uri(cfg);
     let mut options = mongodb::options::ClientOptions::parse(&uri).await?;
-    options.server_selection_timeout = Some(std::time::Duration::from_secs(3));
-    options.connect_timeout = Some(std::time::Duration::from_secs(3));
+    let timeout = Duration::from_secs(mongo.connect_timeout.unwrap_or(3));
+    options.server_selection_timeout = Some(timeout);
+    options.connect_timeout = Some(timeout);
+    if let Some(tls) = &mongo.tls {
+        options.tls = Some(driver_tls(tls));
+    }
     let client = Client::with_options(options)?;
     Ok(client)
 }
 
+/// TLS of the driver. MongoDB has no fallback to plain connections, `prefer`
+/// requires TLS, and the driver cannot skip only the host name check, so
+/// `verify-ca` verifies fully.
+fn driver_tls(tls: &TlsOptions) -> Tls {
+    if tls.mode == TlsMode::Disable {
+        return Tls::Disabled;
+    }
+    let mut options = mongodb::options::TlsOptions::default();
+    options.allow_invalid_certificates = Some(matches!(tls.mode, TlsMode::Prefer | TlsMode::Require));
+    options.ca_file_path = tls.ca_file.as_ref().map(Into::into);
+    options.cert_key_file_path = tls.cert_file.as_ref().map(Into::into);
+    Tls::Enabled(options)
+}
+
+/// TLS arguments of mongodump and mongorestore, as `driver_tls` for the driver
+fn tool_tls_args(tls: &TlsOptions) -> Vec<String> {
+    let mut args = Vec::new();
+    match tls.mode {
+        TlsMode::Disable => return args,
+        TlsMode::Prefer | TlsMode::Require => args.push("--tlsInsecure".into()),
+        TlsMode::VerifyCa => args.push("--tlsAllowInvalidHostnames".into()),
+        TlsMode::VerifyFull => {}
+    }
+    args.insert(0, "--tls".into());
+    if let Some(ca_file) = &tls.ca_file {
+        args.push(format!("--tlsCAFile={}", ca_file));
+    }
+    if let Some(cert_file) = &tls.cert_file {
+        args.push(format!("--tlsCertificateKeyFile={}", cert_file));
+    }
+    args
+}
+
 pub fn select_mongo_path() -> std::path::PathBuf {
     "/usr/local/mongodb/bin".to_string().into()
 }
@@ -18,11 +60,52 @@ pub fn select_mongo_path() -> std::path::PathBuf {
 pub fn get_mongo_uri(cfg: DatabaseConfig) -> String {
     if cfg.username.is_empty() {
         format!("mongodb://{}:{}/{}", cfg.host, cfg.port, cfg.database)
-
     } else {
         format!(
             "mongodb://{}:{}@{}:{}/{}?authSource=admin",
-            cfg.username, cfg.password, cfg.host, cfg.port, cfg.database
+            cfg.username, cfg.password.expose(), cfg.host, cfg.port, cfg.database
         )
     }
 }
+
+/// Connection arguments for mongodump and mongorestore, with the password to
+/// write to their stdin. The tools prompt for it when the URI has a user but
+/// no password, which keeps it out of `ps` and off the disk.
+/// TLS and the connection timeout of `options` are passed along.
+pub fn tool_args(cfg: &DatabaseConfig) -> (Vec<String>, Option<String>) {
+    let options = cfg.mongo_options();
+    let mut extra = Vec::new();
+    if let Some(tls) = &options.tls {
+        extra.extend(tool_tls_args(tls));
+    }
+    let with_timeout = |uri: String| match options.connect_timeout {
+        Some(timeout) => {
+            let separator = if uri.contains('?') { '&' } else { '?' };
+            format!("{}{}connectTimeoutMS={}", uri, separator, timeout * 1000)
+        }
+        None => uri,
+    };
+
+    if cfg.username.is_empty() || cfg.password.is_empty() {
+        let mut args = vec![format!("--uri={}", with_timeout(get_mongo_uri(cfg.clone())))];
+        args.extend(extra);
+        return (args, None);
+    }
+
+    let uri = with_timeout(format!(
+        "mongodb://{}@{}:{}/{}?authSource=admin",
+        cfg.username, cfg.host, cfg.port, cfg.database
+    ));
+    let mut args = vec![format!("--uri={}", uri)];
+    args.extend(extra);
+    (args, Some(format!("{}\n", cfg.password.expose())))
+}
+
+/// Databases of the server, as listed by `listDatabases`
+pub async fn list_databases(cfg: &DatabaseConfig) -> Result<Vec<String>> {
+    within(cfg.query_timeout(), async {
+        let client = connect(cfg.clone()).await?;
+        Ok(client.list_database_names().await?)
+    })
+    .await
+}
@@ -2,8 +2,8 @@ use anyhow::Result;
 use async_trait::async_trait;
 use std::path::{Path, PathBuf};
 
-use super::{backup, ping, restore};
-use crate::domain::factory::Database;
+use super::{backup, inventory, ping, restore};
+use crate::domain::factory::{Database, Inventory, RestoreScope};
 use crate::services::config::DatabaseConfig;
 use crate::utils::locks::{DbOpLock, FileLock};
 
@@ -39,10 +39,16 @@ impl Database for MongoDatabase {
         res
     }
 
-    async fn restore(&self, file: &Path) -> Result<()> {
+    /// `mongorestore --drop` only drops the collections of the archive,
+    /// whatever the scope
+    async fn restore(&self, file: &Path, _scope: RestoreScope) -> Result<()> {
         FileLock::acquire(&self.cfg.generated_id, DbOpLock::Restore.as_str()).await?;
         let res = restore::run(self.cfg.clone(), file.to_path_buf()).await;
         FileLock::release(&self.cfg.generated_id).await?;
         res
     }
+
+    async fn inventory(&self) -> Result<Inventory> {
+        inventory::run(self.cfg.clone()).await
+    }
 }
@@ -0,0 +1,30 @@
+use crate::domain::factory::Inventory;
+use crate::domain::mongodb::connection::connect;
+use crate::services::config::DatabaseConfig;
+use anyhow::Result;
+use mongodb::bson::{Document, doc};
+use tracing::debug;
+
+pub async fn run(cfg: DatabaseConfig) -> Result<Inventory> {
+    let client = connect(cfg.clone()).await?;
+    let db = client.database(&cfg.database);
+
+    let mut inventory = Inventory::new();
+    for name in db.list_collection_names().await? {
+        if name.starts_with("system.") {
+            continue;
+        }
+        let count = db
+            .collection::<Document>(&name)
+            .count_documents(doc! {})
+            .await?;
+        inventory.insert(name, count);
+    }
+
+    debug!(
+        "MongoDB inventory for {}: {} collections",
+        cfg.name,
+        inventory.len()
+    );
+    Ok(inventory)
+}
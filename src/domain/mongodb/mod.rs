@@ -2,4 +2,7 @@ mod backup;
 mod restore;
 pub mod database;
 mod ping;
-mod connection;
\ No newline at end of file
+mod connection;
+mod inventory;
+
+pub use connection::{list_databases, select_mongo_path};
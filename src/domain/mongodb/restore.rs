@@ -1,23 +1,29 @@
-use crate::domain::mongodb::connection::{get_mongo_uri, select_mongo_path};
+use crate::domain::mongodb::connection::{select_mongo_path, tool_args};
 use crate::services::config::DatabaseConfig;
+use crate::utils::process::ProcessControl;
 use anyhow::{Context, Result};
 use std::path::PathBuf;
 use std::process::Command;
 use tracing::{debug, error, info};
 
 pub async fn run(cfg: DatabaseConfig, restore_file: PathBuf) -> Result<()> {
+    let control = ProcessControl::current(cfg.restore_timeout());
     tokio::task::spawn_blocking(move || -> Result<()> {
         debug!("Starting MongoDB restore for database {}", cfg.name);
 
         let mongorestore = select_mongo_path().join("mongorestore");
-        let uri = get_mongo_uri(cfg.clone());
+        let (connection, password) = tool_args(&cfg);
 
-        let output = Command::new(mongorestore)
-            .arg(format!("--uri={}", uri))
-            .arg(format!("--archive={}", restore_file.display()))
-            .arg("--gzip")
-            .arg("--drop")
-            .output()
+        let output = control
+            .output_with_input(
+                Command::new(mongorestore)
+                    .args(&connection)
+                    .arg(format!("--archive={}", restore_file.display()))
+                    .arg("--gzip")
+                    .arg("--drop")
+                    .args(&cfg.mongo_options().extra_args.restore),
+                password.unwrap_or_default().as_bytes(),
+            )
             .with_context(|| format!("Failed to run mongorestore for {}", cfg.name))?;
 
         if !output.status.success() {
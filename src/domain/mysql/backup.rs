@@ -1,5 +1,6 @@
-use crate::domain::mysql::connection::server_version;
+use crate::domain::mysql::connection::{connection_args, server_version};
 use crate::services::config::DatabaseConfig;
+use crate::utils::process::ProcessControl;
 use anyhow::{Context, Result};
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -12,17 +13,19 @@ pub async fn run(
     env: HashMap<String, String>,
     file_extension: &'static str,
 ) -> Result<PathBuf> {
+    let control = ProcessControl::current(cfg.backup_timeout());
+    let version_control = ProcessControl::current(cfg.query_timeout());
     tokio::task::spawn_blocking(move || -> Result<PathBuf> {
         debug!("Starting backup for database {}", cfg.name);
 
-        let version = match futures::executor::block_on(server_version(&cfg)) {
+        let version = match server_version(&cfg, &version_control) {
             Ok(v) => {
                 debug!("Mysql version detected: {}", v);
                 v
             }
             Err(e) => {
                 error!("Failed to get server version for {}: {:?}", cfg.name, e);
-                return Err(e.into());
+                return Err(e);
             }
         };
 
@@ -30,26 +33,38 @@ pub async fn run(
 
         let file_path = backup_dir.join(format!("{}{}", cfg.generated_id, file_extension));
 
-        let output = Command::new("mysqldump")
-            .arg("--host")
-            .arg(cfg.host)
-            .arg("--port")
-            .arg(cfg.port.to_string())
-            .arg("--user")
-            .arg(cfg.username)
-            .arg("--routines")
-            .arg("--events")
-            .arg("--triggers")
-            .arg("--verbose")
-            .arg("--single-transaction")
-            .arg("--quick")
-            .arg("--add-drop-database")
-            .arg("--databases")
-            .arg(cfg.database)
-            .arg("-r")
-            .arg(&file_path)
-            .envs(env)
-            .output()
+        let options = cfg.mysql_options();
+        let mut selection = Vec::new();
+        for table in options.exclude_tables.iter() {
+            selection.push(format!("--ignore-table={}.{}", cfg.database, table));
+        }
+        if cfg.is_filtered() {
+            // Without --databases the dump neither drops nor creates the
+            // database, so restoring it keeps the tables it leaves out
+            selection.push(cfg.database.clone());
+            selection.extend(options.tables.iter().cloned());
+        } else {
+            selection.push("--add-drop-database".to_string());
+            selection.push("--databases".to_string());
+            selection.push(cfg.database.clone());
+        }
+
+        let output = control
+            .output(
+                Command::new("mysqldump")
+                    .args(connection_args(&cfg))
+                    .arg("--routines")
+                    .arg("--events")
+                    .arg("--triggers")
+                    .arg("--verbose")
+                    .arg("--single-transaction")
+                    .arg("--quick")
+                    .args(&options.extra_args.backup)
+                    .args(selection)
+                    .arg("-r")
+                    .arg(&file_path)
+                    .envs(env),
+            )
             .with_context(|| format!("Failed to run mysqldump for {}", cfg.name))?;
 
         if !output.status.success() {
@@ -1,15 +1,61 @@
 use crate::services::config::DatabaseConfig;
+use crate::services::options::{DEFAULT_CONNECT_TIMEOUT, TlsMode};
+use crate::utils::process::ProcessControl;
 use std::process::Command;
 use anyhow::Result;
 
-pub async fn server_version(cfg: &DatabaseConfig) -> Result<String> {
-    let output = Command::new("mysql")
-        .arg("--host").arg(&cfg.host)
-        .arg("--port").arg(cfg.port.to_string())
-        .arg("--user").arg(&cfg.username)
-        .arg("-e").arg("SELECT VERSION();")
-        .env("MYSQL_PWD", &cfg.password)
-        .output()?;
+/// Identifier between backticks, as a database or table name in a statement
+pub fn quote_ident(ident: &str) -> String {
+    format!("`{}`", ident.replace('`', "``"))
+}
+
+/// Server, user, TLS and connection timeout arguments of the client tools.
+/// TLS flags are those of the MariaDB client shipped with the agent.
+pub fn connection_args(cfg: &DatabaseConfig) -> Vec<String> {
+    let options = cfg.mysql_options();
+    let mut args = vec![
+        "--host".to_string(),
+        cfg.host.clone(),
+        "--port".to_string(),
+        cfg.port.to_string(),
+        "--user".to_string(),
+        cfg.username.clone(),
+    ];
+    args.push(format!(
+        "--connect-timeout={}",
+        options.connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT)
+    ));
+    if let Some(tls) = options.tls {
+        match tls.mode {
+            TlsMode::Disable => args.push("--skip-ssl".into()),
+            TlsMode::Prefer => {}
+            TlsMode::Require => args.push("--ssl".into()),
+            TlsMode::VerifyCa | TlsMode::VerifyFull => {
+                args.push("--ssl".into());
+                args.push("--ssl-verify-server-cert".into());
+            }
+        }
+        for (flag, file) in [
+            ("--ssl-ca", tls.ca_file),
+            ("--ssl-cert", tls.cert_file),
+            ("--ssl-key", tls.key_file),
+        ] {
+            if let Some(file) = file {
+                args.push(format!("{}={}", flag, file));
+            }
+        }
+    }
+    args
+}
+
+/// Blocking, to be called off the async runtime
+pub fn server_version(cfg: &DatabaseConfig, control: &ProcessControl) -> Result<String> {
+    let output = control.output(
+        Command::new("mysql")
+            .args(connection_args(cfg))
+            .arg("-e").arg("SELECT VERSION();")
+            .env("MYSQL_PWD", cfg.password.expose()),
+    )?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -25,3 +71,31 @@ pub async fn server_version(cfg: &DatabaseConfig) -> Result<String> {
 
     Ok(version)
 }
+
+/// Databases of the server, as listed by `SHOW DATABASES`
+pub async fn list_databases(cfg: &DatabaseConfig) -> Result<Vec<String>> {
+    let cfg = cfg.clone();
+    let control = ProcessControl::current(cfg.query_timeout());
+    tokio::task::spawn_blocking(move || -> Result<Vec<String>> {
+        let output = control.output(
+            Command::new("mysql")
+                .args(connection_args(&cfg))
+                .arg("--batch")
+                .arg("--skip-column-names")
+                .arg("-e").arg("SHOW DATABASES;")
+                .env("MYSQL_PWD", cfg.password.expose()),
+        )?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("Database listing failed: {}", stderr);
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect())
+    })
+    .await?
+}
@@ -4,9 +4,9 @@ use async_trait::async_trait;
 use std::path::{Path, PathBuf};
 use super::{
     backup,
-    ping, restore,
+    inventory, ping, restore,
 };
-use crate::domain::factory::Database;
+use crate::domain::factory::{Database, Inventory, RestoreScope};
 use crate::services::config::DatabaseConfig;
 use crate::utils::locks::{DbOpLock, FileLock};
 
@@ -21,7 +21,7 @@ impl MySQLDatabase {
 
     fn build_env(&self) -> HashMap<String, String> {
         let mut envs = std::env::vars().collect::<HashMap<_, _>>();
-        envs.insert("MYSQL_PWD".to_string(), self.cfg.password.clone());
+        envs.insert("MYSQL_PWD".to_string(), self.cfg.password.expose().to_string());
         envs
     }
 }
@@ -43,10 +43,14 @@ impl Database for MySQLDatabase {
         res
     }
 
-    async fn restore(&self, file: &Path) -> Result<()> {
+    async fn restore(&self, file: &Path, scope: RestoreScope) -> Result<()> {
         FileLock::acquire(&self.cfg.generated_id, DbOpLock::Restore.as_str()).await?;
-        let res = restore::run(self.cfg.clone(), file.to_path_buf()).await;
+        let res = restore::run(self.cfg.clone(), file.to_path_buf(), scope).await;
         FileLock::release(&self.cfg.generated_id).await?;
         res
     }
+
+    async fn inventory(&self) -> Result<Inventory> {
+        inventory::run(self.cfg.clone(), self.build_env()).await
+    }
 }
@@ -0,0 +1,87 @@
+use crate::domain::factory::Inventory;
+use crate::domain::mysql::connection::{connection_args, quote_ident};
+use crate::services::config::DatabaseConfig;
+use crate::utils::process::ProcessControl;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::process::Command;
+use tracing::debug;
+
+fn query(
+    cfg: &DatabaseConfig,
+    env: &HashMap<String, String>,
+    control: &ProcessControl,
+    sql: &str,
+) -> Result<String> {
+    let output = control
+        .output(
+            Command::new("mysql")
+                .args(connection_args(cfg))
+                .arg("--batch")
+                .arg("--skip-column-names")
+                .arg("-e")
+                .arg(sql)
+                .envs(env),
+        )
+        .with_context(|| format!("Failed to query MySQL server {}", cfg.name))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("Inventory query failed for {}: {}", cfg.name, stderr);
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+pub async fn run(cfg: DatabaseConfig, env: HashMap<String, String>) -> Result<Inventory> {
+    // Counting rows reads every table, like a dump does
+    let control = ProcessControl::current(cfg.backup_timeout());
+    tokio::task::spawn_blocking(move || -> Result<Inventory> {
+        let tables_sql = format!(
+            "SELECT table_name FROM information_schema.tables \
+             WHERE table_schema = '{}' AND table_type = 'BASE TABLE';",
+            cfg.database.replace('\'', "''")
+        );
+        let tables: Vec<String> = query(&cfg, &env, &control, &tables_sql)?
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect();
+
+        let mut inventory = Inventory::new();
+        if tables.is_empty() {
+            return Ok(inventory);
+        }
+
+        let counts_sql = tables
+            .iter()
+            .map(|t| {
+                format!(
+                    "SELECT '{}', COUNT(*) FROM {}.{}",
+                    t.replace('\'', "''"),
+                    quote_ident(&cfg.database),
+                    quote_ident(t)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" UNION ALL ");
+
+        for line in query(&cfg, &env, &control, &counts_sql)?.lines() {
+            if let Some((table, count)) = line.rsplit_once('\t') {
+                let count = count
+                    .trim()
+                    .parse::<u64>()
+                    .with_context(|| format!("Invalid row count for table {}", table))?;
+                inventory.insert(table.to_string(), count);
+            }
+        }
+
+        debug!(
+            "MySQL inventory for {}: {} tables",
+            cfg.name,
+            inventory.len()
+        );
+        Ok(inventory)
+    })
+    .await?
+}
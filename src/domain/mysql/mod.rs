@@ -2,4 +2,7 @@ pub mod backup;
 pub mod database;
 mod restore;
 mod ping;
-mod connection;
\ No newline at end of file
+mod connection;
+mod inventory;
+
+pub use connection::list_databases;
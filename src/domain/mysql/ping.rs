@@ -1,19 +1,22 @@
+use crate::domain::mysql::connection::connection_args;
 use crate::services::config::DatabaseConfig;
+use crate::utils::process::ProcessControl;
 use anyhow::Context;
 use std::collections::HashMap;
 use std::process::Command;
 
 pub async fn run(cfg: DatabaseConfig, env: HashMap<String, String>) -> anyhow::Result<bool> {
-    let output = Command::new("mysqladmin")
-        .arg("--host")
-        .arg(cfg.host)
-        .arg("--port")
-        .arg(cfg.port.to_string())
-        .arg("--user")
-        .arg(cfg.username)
-        .arg("ping")
-        .envs(env)
-        .output()
-        .with_context(|| format!("Failed to ping MySQL server {}", cfg.name))?;
-    Ok(output.status.success())
+    let control = ProcessControl::current(cfg.query_timeout());
+    tokio::task::spawn_blocking(move || -> anyhow::Result<bool> {
+        let output = control
+            .output(
+                Command::new("mysqladmin")
+                    .args(connection_args(&cfg))
+                    .arg("ping")
+                    .envs(env),
+            )
+            .with_context(|| format!("Failed to ping MySQL server {}", cfg.name))?;
+        Ok(output.status.success())
+    })
+    .await?
 }
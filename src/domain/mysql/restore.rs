@@ -5,9 +5,18 @@ use tracing::{debug, error, info};
 use std::path::PathBuf;
 use std::process::Command;
 
+use crate::domain::factory::RestoreScope;
+use crate::domain::mysql::connection::{connection_args, quote_ident};
 use crate::services::config::DatabaseConfig;
+use crate::utils::process::ProcessControl;
+use crate::utils::progress::Progress;
 
-pub async fn run(cfg: DatabaseConfig, restore_file: PathBuf) -> Result<()> {
+/// Size of the writes to mysql's stdin, between which progress is updated
+const FEED_CHUNK_SIZE: usize = 1024 * 1024;
+
+pub async fn run(cfg: DatabaseConfig, restore_file: PathBuf, scope: RestoreScope) -> Result<()> {
+    let control = ProcessControl::current(cfg.restore_timeout());
+    let progress = Progress::current();
     let handle = tokio::task::spawn_blocking(move || -> Result<()> {
         debug!("Starting restore for database {}", cfg.name);
 
@@ -17,51 +26,64 @@ pub async fn run(cfg: DatabaseConfig, restore_file: PathBuf) -> Result<()> {
         file.read_to_string(&mut sql_content)
             .with_context(|| format!("Failed to read restore file {}", restore_file.display()))?;
 
-        let drop_create_cmd = format!(
-            "DROP DATABASE IF EXISTS {0}; CREATE DATABASE {0};",
-            cfg.database
-        );
+        // A partial restore replaces the tables of the dump only
+        let database = quote_ident(&cfg.database);
+        let drop_create_cmd = match scope {
+            RestoreScope::Full => format!(
+                "DROP DATABASE IF EXISTS {0}; CREATE DATABASE {0};",
+                database
+            ),
+            RestoreScope::Partial => format!("CREATE DATABASE IF NOT EXISTS {};", database),
+        };
 
-        let drop_status = Command::new("mysql")
-            .arg("--host")
-            .arg(&cfg.host)
-            .arg("--port")
-            .arg(cfg.port.to_string())
-            .arg("--user")
-            .arg(&cfg.username)
-            .arg("-e")
-            .arg(&drop_create_cmd)
-            .env("MYSQL_PWD", &cfg.password)
-            .status()
+        let drop_status = control
+            .status(
+                Command::new("mysql")
+                    .args(connection_args(&cfg))
+                    .arg("-e")
+                    .arg(&drop_create_cmd)
+                    .env("MYSQL_PWD", cfg.password.expose()),
+            )
             .with_context(|| format!("Failed to drop/recreate database {}", cfg.name))?;
 
         if !drop_status.success() {
             error!("Drop/create database failed for {}", cfg.name);
             anyhow::bail!("Failed to drop/recreate database {}", cfg.name);
         }
-        info!("Database {} dropped and recreated", cfg.name);
+        match scope {
+            RestoreScope::Full => info!("Database {} dropped and recreated", cfg.name),
+            RestoreScope::Partial => {
+                info!("Database {} kept, restoring the dumped tables only", cfg.name)
+            }
+        }
 
-        let mut child = Command::new("mysql")
-            .arg("--host")
-            .arg(&cfg.host)
-            .arg("--port")
-            .arg(cfg.port.to_string())
-            .arg("--user")
-            .arg(&cfg.username)
-            .arg(&cfg.database)
-            .env("MYSQL_PWD", &cfg.password)
-            .stdin(std::process::Stdio::piped())
-            .spawn()
+        let mut child = control
+            .spawn(
+                Command::new("mysql")
+                    .args(connection_args(&cfg))
+                    .args(&cfg.mysql_options().extra_args.restore)
+                    .arg(&cfg.database)
+                    .env("MYSQL_PWD", cfg.password.expose())
+                    .stdin(std::process::Stdio::piped()),
+            )
             .with_context(|| format!("Failed to start mysql restore for {}", cfg.name))?;
 
         let mut stdin = child.stdin.take().context("Failed to open child stdin")?;
-        stdin.write_all(sql_content.as_bytes())
-            .context("Failed to write SQL content to mysql stdin")?;
-        stdin.flush()?;
-        drop(stdin);
-
-        let output = child
-            .wait_with_output()
+        let pid = child.id();
+        // Feeding stdin blocks as well when mysql hangs, so it is supervised too
+        let output = control
+            .supervise(pid, || {
+                let total = sql_content.len() as u64;
+                let mut written = 0;
+                for chunk in sql_content.as_bytes().chunks(FEED_CHUNK_SIZE) {
+                    stdin.write_all(chunk)?;
+                    written += chunk.len() as u64;
+                    progress.bytes(written, Some(total));
+                }
+                stdin.flush()?;
+                drop(stdin);
+                child.wait_with_output()
+            })
             .with_context(|| format!("Failed to complete mysql restore for {}", cfg.name))?;
 
         if !output.status.success() {
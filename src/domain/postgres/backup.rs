@@ -3,50 +3,79 @@ use tracing::{debug, error, info};
 use std::path::PathBuf;
 use std::process::Command;
 
-use super::connection::{select_pg_path, server_version};
+use super::connection::{client_env, select_pg_path, server_version};
 use super::format::PostgresDumpFormat;
 use crate::services::config::DatabaseConfig;
+use crate::services::options::PostgresOptions;
+use crate::utils::process::{ProcessControl, within};
+use crate::utils::progress::{Phase, Progress};
+
+/// Object filters and extra arguments of `options`, common to both formats
+fn dump_args(options: &PostgresOptions) -> Vec<String> {
+    let mut args = Vec::new();
+    for (flag, values) in [
+        ("-n", &options.schemas),
+        ("-N", &options.exclude_schemas),
+        ("-t", &options.tables),
+        ("-T", &options.exclude_tables),
+    ] {
+        for value in values {
+            args.push(flag.to_string());
+            args.push(value.clone());
+        }
+    }
+    args.extend(options.extra_args.backup.iter().cloned());
+    args
+}
 
 pub async fn run(
     cfg: DatabaseConfig,
     format: PostgresDumpFormat,
     backup_dir: PathBuf,
 ) -> Result<PathBuf> {
-    tokio::task::spawn_blocking(move || -> Result<PathBuf> {
-        debug!("Starting backup for database {}", cfg.name);
+    let control = ProcessControl::current(cfg.backup_timeout());
+    let progress = Progress::current();
+    debug!("Starting backup for database {}", cfg.name);
 
-        let version = match futures::executor::block_on(server_version(&cfg)) {
-            Ok(v) => {
-                debug!("Postgres version detected: {}", v);
-                v
-            }
-            Err(e) => {
-                error!("Failed to get server version for {}: {:?}", cfg.name, e);
-                return Err(e.into());
-            }
-        };
+    let version = match within(cfg.query_timeout(), server_version(&cfg)).await {
+        Ok(v) => {
+            debug!("Postgres version detected: {}", v);
+            v
+        }
+        Err(e) => {
+            error!("Failed to get server version for {}: {:?}", cfg.name, e);
+            return Err(e);
+        }
+    };
+
+    tokio::task::spawn_blocking(move || -> Result<PathBuf> {
 
         let pg_dump = select_pg_path(&version).join("pg_dump");
         debug!("Using pg_dump at {:?}", pg_dump);
 
+        let options = cfg.postgres_options();
+
         match format {
             PostgresDumpFormat::Fc => {
                 info!("Running FC backup for {}", cfg.name);
                 let file_path = backup_dir.join(format!("{}.dump", cfg.generated_id));
                 let url = format!(
-                    "postgresql://{}:{}@{}:{}/{}",
-                    cfg.username, cfg.password, cfg.host, cfg.port, cfg.database
+                    "postgresql://{}@{}:{}/{}",
+                    cfg.username, cfg.host, cfg.port, cfg.database
                 );
 
-                let status = Command::new(&pg_dump)
-                    .arg("--dbname")
-                    .arg(&url)
-                    .arg("-Fc")
-                    .arg("-f")
-                    .arg(&file_path)
-                    .arg("-v")
-                    .arg("--compress=3")
-                    .status();
+                let status = control.status(
+                    Command::new(&pg_dump)
+                        .arg("--dbname")
+                        .arg(&url)
+                        .arg("-Fc")
+                        .arg("-f")
+                        .arg(&file_path)
+                        .arg("-v")
+                        .arg(format!("--compress={}", options.compress.unwrap_or(3)))
+                        .args(dump_args(&options))
+                        .envs(client_env(&cfg)),
+                );
 
                 match status {
                     Ok(s) if s.success() => info!(
@@ -80,20 +109,24 @@ pub async fn run(
                 }
 
                 let url = format!(
-                    "postgresql://{}:{}@{}:{}/{}",
-                    cfg.username, cfg.password, cfg.host, cfg.port, cfg.database
+                    "postgresql://{}@{}:{}/{}",
+                    cfg.username, cfg.host, cfg.port, cfg.database
                 );
 
-                let status = Command::new(&pg_dump)
-                    .arg("--dbname")
-                    .arg(&url)
-                    .arg("-Fd")
-                    .arg("-j")
-                    .arg("4")
-                    .arg("-f")
-                    .arg(&dump_dir)
-                    .arg("-v")
-                    .status();
+                let status = control.status(
+                    Command::new(&pg_dump)
+                        .arg("--dbname")
+                        .arg(&url)
+                        .arg("-Fd")
+                        .arg("-j")
+                        .arg(options.jobs.unwrap_or(4).to_string())
+                        .arg("-f")
+                        .arg(&dump_dir)
+                        .arg("-v")
+                        .args(options.compress.map(|level| format!("--compress={}", level)))
+                        .args(dump_args(&options))
+                        .envs(client_env(&cfg)),
+                );
 
                 match status {
                     Ok(s) if s.success() => {
@@ -112,6 +145,9 @@ pub async fn run(
                     }
                 }
 
+                progress.phase(Phase::Archiving);
+                progress.watch(&backup_dir);
+
                 match std::fs::File::create(&tar_file) {
                     Ok(tar_gz) => {
                         let enc =
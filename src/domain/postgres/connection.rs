@@ -1,23 +1,102 @@
 use std::path::Path;
 use crate::domain::postgres::format::PostgresDumpFormat;
 use crate::services::config::DatabaseConfig;
+use crate::services::options::{DEFAULT_CONNECT_TIMEOUT, TlsMode, TlsOptions};
+use crate::utils::process::within;
 use anyhow::Result;
+use openssl::ssl::{SslConnector, SslFiletype, SslMethod, SslVerifyMode};
+use postgres_openssl::MakeTlsConnector;
+use std::future::Future;
 use tokio_postgres::{Client, NoTls};
 use tracing::info;
 
 pub async fn connect(cfg: &DatabaseConfig) -> Result<Client> {
-    let dsn = format!(
+    let options = cfg.postgres_options();
+    let mut dsn = format!(
         "host={} port={} user={} password={} dbname={}",
-        cfg.host, cfg.port, cfg.username, cfg.password, cfg.database
+        cfg.host, cfg.port, cfg.username, cfg.password.expose(), cfg.database
     );
+    let timeout = options.connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT);
+    dsn.push_str(&format!(" connect_timeout={}", timeout));
 
-    let (client, connection) = tokio_postgres::connect(&dsn, NoTls).await?;
+    let Some(tls) = options.tls else {
+        let (client, connection) = tokio_postgres::connect(&dsn, NoTls).await?;
+        drive(connection);
+        return Ok(client);
+    };
+    dsn.push_str(match tls.mode {
+        TlsMode::Disable => " sslmode=disable",
+        TlsMode::Prefer => " sslmode=prefer",
+        // Certificate checks are left to the connector
+        _ => " sslmode=require",
+    });
+    let (client, connection) = tokio_postgres::connect(&dsn, tls_connector(&tls)?).await?;
+    drive(connection);
+    Ok(client)
+}
+
+fn drive(connection: impl Future<Output = Result<(), tokio_postgres::Error>> + Send + 'static) {
     tokio::spawn(async move {
         if let Err(e) = connection.await {
             tracing::error!("Postgres connection error: {}", e);
         }
     });
-    Ok(client)
+}
+
+/// OpenSSL connector checking the server certificate as far as `mode` asks
+fn tls_connector(tls: &TlsOptions) -> Result<MakeTlsConnector> {
+    let mut builder = SslConnector::builder(SslMethod::tls())?;
+    if let Some(ca_file) = &tls.ca_file {
+        builder.set_ca_file(ca_file)?;
+    }
+    if let Some(cert_file) = &tls.cert_file {
+        builder.set_certificate_chain_file(cert_file)?;
+    }
+    if let Some(key_file) = &tls.key_file {
+        builder.set_private_key_file(key_file, SslFiletype::PEM)?;
+    }
+    if matches!(tls.mode, TlsMode::Prefer | TlsMode::Require) {
+        builder.set_verify(SslVerifyMode::NONE);
+    }
+
+    let mut connector = MakeTlsConnector::new(builder.build());
+    if tls.mode == TlsMode::VerifyCa {
+        connector.set_callback(|config, _| {
+            config.set_verify_hostname(false);
+            Ok(())
+        });
+    }
+    Ok(connector)
+}
+
+/// Environment of the libpq tools: password, TLS and connection timeout
+pub fn client_env(cfg: &DatabaseConfig) -> Vec<(&'static str, String)> {
+    let options = cfg.postgres_options();
+    let timeout = options.connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT);
+    let mut env = vec![
+        ("PGPASSWORD", cfg.password.expose().to_string()),
+        ("PGCONNECT_TIMEOUT", timeout.to_string()),
+    ];
+    if let Some(tls) = options.tls {
+        let mode = match tls.mode {
+            TlsMode::Disable => "disable",
+            TlsMode::Prefer => "prefer",
+            TlsMode::Require => "require",
+            TlsMode::VerifyCa => "verify-ca",
+            TlsMode::VerifyFull => "verify-full",
+        };
+        env.push(("PGSSLMODE", mode.to_string()));
+        for (name, file) in [
+            ("PGSSLROOTCERT", tls.ca_file),
+            ("PGSSLCERT", tls.cert_file),
+            ("PGSSLKEY", tls.key_file),
+        ] {
+            if let Some(file) = file {
+                env.push((name, file));
+            }
+        }
+    }
+    env
 }
 
 pub async fn server_version(cfg: &DatabaseConfig) -> Result<String> {
@@ -53,6 +132,27 @@ pub async fn terminate_connections(cfg: &DatabaseConfig) -> Result<()> {
     Ok(())
 }
 
+/// Create the database of `cfg` unless it exists
+pub async fn ensure_database(cfg: &DatabaseConfig) -> Result<()> {
+    let mut admin = cfg.clone();
+    admin.database = "postgres".into();
+
+    let client = connect(&admin).await?;
+    let exists = client
+        .query_opt("SELECT 1 FROM pg_database WHERE datname = $1;", &[&cfg.database])
+        .await?
+        .is_some();
+    if !exists {
+        let name = format!("\"{}\"", cfg.database.replace('"', "\"\""));
+        client
+            .batch_execute(&format!("CREATE DATABASE {};", name))
+            .await?;
+        info!("Database {} created", cfg.database);
+    }
+
+    Ok(())
+}
+
 pub fn detect_format_from_file(restore_file: &Path) -> PostgresDumpFormat {
     match restore_file.extension().and_then(|e| e.to_str()) {
         Some("dump") => PostgresDumpFormat::Fc,
@@ -92,3 +192,22 @@ pub async fn detect_format_from_size(cfg: &DatabaseConfig) -> PostgresDumpFormat
         PostgresDumpFormat::Fc
     }
 }
+
+/// Databases of the server that accept connections, templates excluded
+pub async fn list_databases(cfg: &DatabaseConfig) -> Result<Vec<String>> {
+    let mut admin = cfg.clone();
+    admin.database = "postgres".into();
+
+    within(cfg.query_timeout(), async {
+        let client = connect(&admin).await?;
+        let rows = client
+            .query(
+                "SELECT datname FROM pg_database WHERE NOT datistemplate AND datallowconn;",
+                &[],
+            )
+            .await?;
+
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    })
+    .await
+}
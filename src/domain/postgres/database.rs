@@ -5,9 +5,9 @@ use std::path::{Path, PathBuf};
 use super::{
     backup,
     format::PostgresDumpFormat,
-    ping, restore,
+    inventory, ping, restore,
 };
-use crate::domain::factory::Database;
+use crate::domain::factory::{Database, Inventory, RestoreScope};
 use crate::services::config::DatabaseConfig;
 use crate::utils::locks::{DbOpLock, FileLock};
 
@@ -44,10 +44,14 @@ impl Database for PostgresDatabase {
         res
     }
 
-    async fn restore(&self, file: &Path) -> Result<()> {
+    async fn restore(&self, file: &Path, scope: RestoreScope) -> Result<()> {
         FileLock::acquire(&self.cfg.generated_id, DbOpLock::Restore.as_str()).await?;
-        let res = restore::run(self.cfg.clone(), self.format, file.to_path_buf()).await;
+        let res = restore::run(self.cfg.clone(), self.format, file.to_path_buf(), scope).await;
         FileLock::release(&self.cfg.generated_id).await?;
         res
     }
+
+    async fn inventory(&self) -> Result<Inventory> {
+        inventory::run(self.cfg.clone()).await
+    }
 }
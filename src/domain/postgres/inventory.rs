@@ -0,0 +1,45 @@
+use super::connection::connect;
+use crate::domain::factory::Inventory;
+use crate::services::config::DatabaseConfig;
+use anyhow::Result;
+use tracing::debug;
+
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+pub async fn run(cfg: DatabaseConfig) -> Result<Inventory> {
+    let client = connect(&cfg).await?;
+
+    let tables = client
+        .query(
+            r#"
+            SELECT table_schema, table_name
+            FROM information_schema.tables
+            WHERE table_type = 'BASE TABLE'
+              AND table_schema NOT IN ('pg_catalog', 'information_schema');
+            "#,
+            &[],
+        )
+        .await?;
+
+    let mut inventory = Inventory::new();
+    for row in tables {
+        let schema: String = row.get(0);
+        let table: String = row.get(1);
+        let query = format!(
+            "SELECT count(*) FROM {}.{};",
+            quote_ident(&schema),
+            quote_ident(&table)
+        );
+        let count: i64 = client.query_one(query.as_str(), &[]).await?.get(0);
+        inventory.insert(format!("{}.{}", schema, table), count as u64);
+    }
+
+    debug!(
+        "Postgres inventory for {}: {} tables",
+        cfg.name,
+        inventory.len()
+    );
+    Ok(inventory)
+}
@@ -4,5 +4,9 @@ mod restore;
 mod connection;
 mod format;
 mod ping;
+mod inventory;
 
-pub use connection::{detect_format_from_size, detect_format_from_file};
+pub use connection::{
+    detect_format_from_file, detect_format_from_size, list_databases, select_pg_path, server_version,
+};
+pub use format::PostgresDumpFormat;
@@ -3,59 +3,84 @@ use tracing::{debug, error, info};
 use std::path::PathBuf;
 use std::process::Command;
 
-use super::connection::{select_pg_path, server_version, terminate_connections};
+use super::connection::{
+    client_env, ensure_database, select_pg_path, server_version, terminate_connections,
+};
 use super::format::PostgresDumpFormat;
+use crate::domain::factory::RestoreScope;
 use crate::services::config::DatabaseConfig;
+use crate::utils::process::{ProcessControl, within};
 
 pub async fn run(
     cfg: DatabaseConfig,
     format: PostgresDumpFormat,
     restore_file: PathBuf,
+    scope: RestoreScope,
 ) -> Result<()> {
-    tokio::task::spawn_blocking(move || -> Result<()> {
-        debug!("Starting restore for database {}", cfg.name);
-        let version = match futures::executor::block_on(server_version(&cfg)) {
-            Ok(v) => {
-                debug!("Postgres version detected: {}", v);
-                v
-            }
-            Err(e) => {
-                error!("Failed to get server version for {}: {:?}", cfg.name, e);
-                return Err(e.into());
-            }
-        };
+    let control = ProcessControl::current(cfg.restore_timeout());
+    debug!("Starting restore for database {}", cfg.name);
+    let version = match within(cfg.query_timeout(), server_version(&cfg)).await {
+        Ok(v) => {
+            debug!("Postgres version detected: {}", v);
+            v
+        }
+        Err(e) => {
+            error!("Failed to get server version for {}: {:?}", cfg.name, e);
+            return Err(e);
+        }
+    };
 
-        let pg_restore = select_pg_path(&version).join("pg_restore");
-        debug!("Using pg_restore at {:?}", pg_restore);
+    let pg_restore = select_pg_path(&version).join("pg_restore");
+    debug!("Using pg_restore at {:?}", pg_restore);
 
-        if let Err(e) = futures::executor::block_on(terminate_connections(&cfg)) {
-            error!("Failed to terminate connections for {}: {:?}", cfg.name, e);
-            return Err(e.into());
-        }
-        info!("Connections terminated for database {}", cfg.name);
+    if let Err(e) = within(cfg.query_timeout(), terminate_connections(&cfg)).await {
+        error!("Failed to terminate connections for {}: {:?}", cfg.name, e);
+        return Err(e);
+    }
+    info!("Connections terminated for database {}", cfg.name);
 
+    if scope == RestoreScope::Partial
+        && let Err(e) = within(cfg.query_timeout(), ensure_database(&cfg)).await
+    {
+        error!("Failed to create database {}: {:?}", cfg.name, e);
+        return Err(e);
+    }
+
+    tokio::task::spawn_blocking(move || -> Result<()> {
+
+        // A full restore recreates the database from the maintenance one, a
+        // partial one only replaces the objects of the dump inside the database.
+        // The password goes through PGPASSWORD, never on the command line.
+        let (dbname, recreate): (&str, &[&str]) = match scope {
+            RestoreScope::Full => ("postgres", &["--create"]),
+            RestoreScope::Partial => (&cfg.database, &[]),
+        };
         let url = format!(
-            "postgresql://{}:{}@{}:{}/postgres",
-            cfg.username, cfg.password, cfg.host, cfg.port
+            "postgresql://{}@{}:{}/{}",
+            cfg.username, cfg.host, cfg.port, dbname
         );
 
         debug!("Restore URL: {}", url);
 
+        let options = cfg.postgres_options();
+
         match format {
             PostgresDumpFormat::Fc => {
                 info!("Running FC restore for {}", cfg.name);
-                let status = Command::new(&pg_restore)
-                    .arg("--no-owner")
-                    .arg("--no-privileges")
-                    .arg("--clean")
-                    .arg("--if-exists")
-                    .arg("--create")
-                    .arg("--dbname")
-                    .arg(&url)
-                    .arg("-v")
-                    .arg(&restore_file)
-                    .env("PGPASSWORD", &cfg.password)
-                    .status();
+                let status = control.status(
+                    Command::new(&pg_restore)
+                        .arg("--no-owner")
+                        .arg("--no-privileges")
+                        .arg("--clean")
+                        .arg("--if-exists")
+                        .args(recreate)
+                        .arg("--dbname")
+                        .arg(&url)
+                        .arg("-v")
+                        .args(&options.extra_args.restore)
+                        .arg(&restore_file)
+                        .envs(client_env(&cfg)),
+                );
 
                 match status {
                     Ok(s) if s.success() => {
@@ -106,17 +131,15 @@ pub async fn run(
                 }
 
                 debug!("Listing contents of temp dir: {}", tmp_dir.path().display());
-                for entry in std::fs::read_dir(tmp_dir.path())? {
-                    if let Ok(entry) = entry {
-                        let path = entry.path();
-                        let file_type = entry.file_type()?;
-                        debug!(
-                            " - {} | is_dir: {} | is_file: {}",
-                            path.display(),
-                            file_type.is_dir(),
-                            file_type.is_file()
-                        );
-                    }
+                for entry in std::fs::read_dir(tmp_dir.path())?.flatten() {
+                    let path = entry.path();
+                    let file_type = entry.file_type()?;
+                    debug!(
+                        " - {} | is_dir: {} | is_file: {}",
+                        path.display(),
+                        file_type.is_dir(),
+                        file_type.is_file()
+                    );
                 }
 
                 let dump_dir = if tmp_dir.path().join("toc.dat").exists() {
@@ -129,20 +152,22 @@ pub async fn run(
                         .ok_or_else(|| anyhow::anyhow!("Invalid FD archive: toc.dat not found"))?
                 };
 
-                let status = Command::new(&pg_restore)
-                    .arg("--no-owner")
-                    .arg("--no-privileges")
-                    .arg("--clean")
-                    .arg("--if-exists")
-                    .arg("--create")
-                    .arg("--dbname")
-                    .arg(&url)
-                    .arg("-v")
-                    .arg("-j")
-                    .arg("4")
-                    .arg(dump_dir)
-                    .env("PGPASSWORD", &cfg.password)
-                    .status();
+                let status = control.status(
+                    Command::new(&pg_restore)
+                        .arg("--no-owner")
+                        .arg("--no-privileges")
+                        .arg("--clean")
+                        .arg("--if-exists")
+                        .args(recreate)
+                        .arg("--dbname")
+                        .arg(&url)
+                        .arg("-v")
+                        .arg("-j")
+                        .arg(options.jobs.unwrap_or(4).to_string())
+                        .args(&options.extra_args.restore)
+                        .arg(dump_dir)
+                        .envs(client_env(&cfg)),
+                );
 
                 match status {
                     Ok(s) if s.success() => {
@@ -1,3 +1,4 @@
+mod cli;
 mod core;
 mod domain;
 mod services;
@@ -5,14 +6,21 @@ mod settings;
 mod tasks;
 mod utils;
 
+use crate::tasks::config::config_watch_loop;
+use crate::tasks::http::http_server;
+use crate::tasks::outbox::outbox_loop;
 use crate::tasks::ping::ping_server;
 use crate::utils::locks::FileLock;
-use utils::redis_client;
 use utils::task_manager::scheduler;
+use utils::task_manager::store::schedule_store;
 use crate::utils::logging;
 
 #[tokio::main]
 async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = cli::run(&args).await {
+        std::process::exit(code);
+    }
 
     logging::init_logger();
 
@@ -21,8 +29,8 @@ async fn main() {
         eprintln!("Failed to clean locks on startup: {:?}", e);
     }
 
-    tokio::join!(ping_server(), async {
-        let conn = redis_client::redis_connection().await;
-        scheduler::scheduler_loop(conn).await;
+    tokio::join!(ping_server(), outbox_loop(), config_watch_loop(), http_server(), async {
+        let store = schedule_store().await;
+        scheduler::scheduler_loop(store).await;
     });
 }
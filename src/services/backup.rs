@@ -1,24 +1,82 @@
 #![allow(dead_code)]
 
 use crate::core::context::Context;
-use crate::domain::factory::DatabaseFactory;
-use crate::services::config::{DatabaseConfig, DatabasesConfig, DbType};
+use crate::core::executor::{Executor, Job, Submitted};
+use crate::domain::factory::{DatabaseFactory, Inventory};
+use crate::services::config::{
+    BlackoutAction, ConfigService, DatabaseConfig, DatabasesConfig, DbType, StorageConfig,
+};
+use crate::services::credentials;
+use crate::services::hooks::{self, HookPoint};
+use crate::services::outbox::{DeliveryError, Outbox, OutboxPayload};
+use crate::services::progress::ProgressReporter;
+use crate::services::storage::local::LocalStorage;
+use crate::services::storage::s3::S3Storage;
+use crate::services::storage::sftp::SftpStorage;
+use crate::services::storage::{StoredObject, artifact_name, object_key};
+use crate::services::upload::{ChunkedUpload, UploadOutcome};
+use crate::utils::blackout::ActiveBlackout;
 use crate::utils::common::BackupMethod;
+use crate::utils::crypto;
 use crate::utils::file::full_extension;
+use crate::utils::metrics;
+use crate::utils::process::{cancel_requested, was_cancelled};
+use crate::utils::progress::{Phase, Progress};
 use anyhow::Result;
-use hex;
-use openssl::encrypt::Encrypter;
-use openssl::hash::MessageDigest;
+use chrono::Utc;
 use openssl::pkey::PKey;
 use openssl::rand::rand_bytes;
-use openssl::rsa::Padding;
-use openssl::symm::{Cipher, Crypter, Mode};
+use openssl::symm::Mode;
+use reqwest::Body;
 use reqwest::multipart::{Form, Part};
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
 use tempfile::TempDir;
 use tokio::fs;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+/// Backup result as queued in the outbox until the server acknowledges it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupPayload {
+    pub generated_id: String,
+    pub status: String,
+    pub method: String,
+    pub artifact: Option<ArtifactInfo>,
+    /// Unix time at which the backup finished, used to name stored objects
+    #[serde(default)]
+    pub timestamp: i64,
+    /// Storage targets the artifact was already written to
+    #[serde(default)]
+    pub objects: Vec<StoredObject>,
+    /// Set on `deferred` results, when the blackout window that held the backup closes
+    #[serde(default)]
+    pub deferred_until: Option<i64>,
+    /// Executor job that produced this result
+    #[serde(default)]
+    pub job_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactInfo {
+    /// AES key encrypted with the server public key, hex encoded
+    pub aes_key: String,
+    pub iv: String,
+    pub extension: String,
+    /// Whether the dump leaves out part of the database, unknown for
+    /// artifacts queued before it was recorded
+    #[serde(default)]
+    pub filtered: Option<bool>,
+    /// AES key also sealed with the agent key, hex encoded, for databases
+    /// whose backups the agent verifies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_key: Option<String>,
+    /// Objects of the database and their row counts right after the dump,
+    /// for databases whose backups the agent verifies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inventory: Option<Inventory>,
+}
 
 #[derive(Debug)]
 pub struct BackupResult {
@@ -27,6 +85,10 @@ pub struct BackupResult {
     pub status: String,
     pub backup_file: Option<PathBuf>,
     pub code: Option<String>,
+    /// Whether the dump leaves out schemas, tables or collections
+    pub filtered: bool,
+    /// Inventory taken after the dump when the database is verified
+    pub inventory: Option<Inventory>,
 }
 
 pub struct BackupService {
@@ -40,43 +102,100 @@ impl BackupService {
 
     pub async fn dispatch(
         &self,
-        generated_id: &String,
+        generated_id: &str,
         config: &DatabasesConfig,
         method: BackupMethod,
     ) {
         if let Some(cfg) = config
             .databases
             .iter()
-            .find(|c| c.generated_id == generated_id.as_str())
+            .find(|c| c.generated_id == generated_id)
         {
             let db_cfg = cfg.clone();
             let ctx_clone = self.ctx.clone();
+            let job = Job::new(
+                &cfg.host,
+                method.into(),
+                "backup",
+                generated_id,
+                |job_id| async move {
+                    let generated_id = db_cfg.generated_id.clone();
+                    let work = async {
+                        match TempDir::new() {
+                            Ok(temp_dir) => {
+                                let tmp_path = temp_dir.path().to_path_buf();
+                                info!("Created temp directory {}", tmp_path.display());
 
-            tokio::spawn(async move {
-                match TempDir::new() {
-                    Ok(temp_dir) => {
-                        let tmp_path = temp_dir.path().to_path_buf();
-                        info!("Created temp directory {}", tmp_path.display());
-
-                        match BackupService::run(db_cfg, &tmp_path).await {
-                            Ok(result) => {
-                                let service = BackupService { ctx: ctx_clone };
-                                service.send_result(result, method).await;
+                                let started = Instant::now();
+                                match BackupService::run(db_cfg, &tmp_path).await {
+                                    Ok(result) => {
+                                        metrics::record_backup(
+                                            &generated_id,
+                                            &result.status,
+                                            started.elapsed(),
+                                        );
+                                        let service = BackupService {
+                                            ctx: ctx_clone.clone(),
+                                        };
+                                        service.send_result(result, method, &job_id).await;
+                                    }
+                                    Err(e) => {
+                                        metrics::record_backup(
+                                            &generated_id,
+                                            "failed",
+                                            started.elapsed(),
+                                        );
+                                        error!("Backup error {}", e)
+                                    }
+                                }
+                                // TempDir is automatically deleted when dropped here
                             }
-                            Err(e) => error!("Backup error {}", e),
+                            Err(e) => error!("Failed to create temp dir: {}", e),
                         }
-                        // TempDir is automatically deleted when dropped here
-                    }
-                    Err(e) => error!("Failed to create temp dir: {}", e),
-                }
+                    };
+                    ProgressReporter::track(ctx_clone.clone(), &job_id, &generated_id, "backup", work)
+                        .await;
+                },
+            );
+            let service = BackupService {
+                ctx: self.ctx.clone(),
+            };
+            let cancelled_id = generated_id.to_string();
+            let job = job.on_cancel(|job_id| async move {
+                service
+                    .report_pending(&cancelled_id, method, &job_id, "cancelled")
+                    .await;
             });
+
+            let job_id = job.id.clone();
+            if let Submitted::Queued = Executor::submit(job) {
+                self.report_pending(generated_id, method, &job_id, "queued")
+                    .await;
+            }
         }
     }
 
     pub async fn run(cfg: DatabaseConfig, tmp_path: &Path) -> Result<BackupResult> {
-        let db_instance = DatabaseFactory::create_for_backup(cfg.clone()).await;
         let generated_id = cfg.generated_id.clone();
         let db_type = cfg.db_type.clone();
+        let filtered = cfg.is_filtered();
+
+        let (cfg, _lease) = match credentials::resolve(cfg).await {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                error!("{:#}", e);
+                return Ok(BackupResult {
+                    generated_id,
+                    db_type,
+                    status: "failed".into(),
+                    backup_file: None,
+                    code: None,
+                    filtered,
+                    inventory: None,
+                });
+            }
+        };
+        let db_instance = DatabaseFactory::create_for_backup(cfg.clone()).await;
 
         let reachable = db_instance.ping().await.unwrap_or(false);
         info!("Reachable: {}", reachable);
@@ -87,16 +206,53 @@ impl BackupService {
                 status: "failed".into(),
                 backup_file: None,
                 code: None,
+                filtered,
+                inventory: None,
             });
         }
 
-        match db_instance.backup(tmp_path).await {
+        if let Err(e) = hooks::run(&cfg, HookPoint::PreBackup, None, None).await {
+            error!("{:#}", e);
+            return Ok(BackupResult {
+                generated_id,
+                db_type,
+                status: if was_cancelled(&e) { "cancelled" } else { "failed" }.into(),
+                backup_file: None,
+                code: None,
+                filtered,
+                inventory: None,
+            });
+        }
+
+        let progress = Progress::current();
+        progress.phase(Phase::Dumping);
+        progress.watch(tmp_path);
+
+        let result = db_instance.backup(tmp_path).await;
+        // Compared with the restored copy when verifying this backup
+        let mut inventory = None;
+        if result.is_ok() && cfg.verification.is_some() {
+            match db_instance.inventory().await {
+                Ok(counts) => inventory = Some(counts),
+                Err(e) => warn!("Failed to read the inventory of {}: {:#}", cfg.name, e),
+            }
+        }
+        let (file, status) = match &result {
+            Ok(file) => (Some(file.as_path()), "success"),
+            Err(e) if was_cancelled(e) => (None, "cancelled"),
+            Err(_) => (None, "failed"),
+        };
+        hooks::run_after(&cfg, HookPoint::PostBackup, file, status).await;
+
+        match result {
             Ok(file) => Ok(BackupResult {
                 generated_id,
                 db_type,
                 status: "success".into(),
                 backup_file: Some(file),
                 code: None,
+                filtered,
+                inventory,
             }),
             Err(e) => match e.to_string().as_str() {
                 "backup_already_in_progress" => Ok(BackupResult {
@@ -105,6 +261,17 @@ impl BackupService {
                     status: "failed".into(),
                     backup_file: None,
                     code: Some(e.to_string()),
+                    filtered,
+                    inventory: None,
+                }),
+                _ if was_cancelled(&e) => Ok(BackupResult {
+                    generated_id,
+                    db_type,
+                    status: "cancelled".into(),
+                    backup_file: None,
+                    code: None,
+                    filtered,
+                    inventory: None,
                 }),
                 _ => Ok(BackupResult {
                     generated_id,
@@ -112,12 +279,14 @@ impl BackupService {
                     status: "failed".into(),
                     backup_file: None,
                     code: None,
+                    filtered,
+                    inventory: None,
                 }),
             },
         }
     }
 
-    pub async fn send_result(&self, result: BackupResult, method: BackupMethod) {
+    pub async fn send_result(&self, result: BackupResult, method: BackupMethod, job_id: &str) {
         if result.code.as_deref() == Some("backup_already_in_progress") {
             info!(
                 "[BackupService] Skipping send for DB {}: backup already in progress",
@@ -134,86 +303,332 @@ impl BackupService {
             result.backup_file
         );
 
+        let mut payload = BackupPayload {
+            generated_id: result.generated_id.clone(),
+            status: result.status.clone(),
+            method: method.to_string(),
+            artifact: None,
+            timestamp: Utc::now().timestamp(),
+            objects: Vec::new(),
+            deferred_until: None,
+            job_id: Some(job_id.to_string()),
+        };
+        let mut artifact_path = None;
+
+        let mut backup_file = result.backup_file.clone();
+        if backup_file.is_some() && cancel_requested() {
+            info!("[BackupService] Job {} cancelled before encryption", job_id);
+            payload.status = "cancelled".into();
+            backup_file = None;
+        }
+
+        if let Some(file_path) = backup_file {
+            Progress::current().phase(Phase::Encrypting);
+            match Outbox::stage().await {
+                Ok(dest) => match self.encrypt(&file_path, &dest, &result).await {
+                    Ok((size, artifact)) => {
+                        metrics::record_artifact(&result.generated_id, size);
+                        artifact_path = Some(dest);
+                        payload.artifact = Some(artifact);
+                    }
+                    Err(e) => {
+                        error!("Failed to encrypt backup file: {:#}", e);
+                        Outbox::unstage(&dest).await;
+                    }
+                },
+                Err(e) => error!("Failed to stage backup artifact: {:#}", e),
+            }
+        }
+
+        self.queue(payload, artifact_path.as_deref()).await;
+    }
+
+    /// Report a requested backup that was not started because of a blackout
+    /// window, `deferred` until it closes or `skipped`
+    pub async fn defer(&self, generated_id: &str, method: BackupMethod, blackout: ActiveBlackout) {
+        let (status, deferred_until) = match blackout.action {
+            BlackoutAction::Defer => ("deferred", Some(blackout.ends_at)),
+            BlackoutAction::Skip => ("skipped", None),
+        };
+        info!(
+            "[BackupService] DB: {} Status: {} (blackout until {})",
+            generated_id, status, blackout.ends_at
+        );
+
+        let payload = BackupPayload {
+            generated_id: generated_id.to_string(),
+            status: status.into(),
+            method: method.to_string(),
+            artifact: None,
+            timestamp: Utc::now().timestamp(),
+            objects: Vec::new(),
+            deferred_until,
+            job_id: None,
+        };
+        self.queue(payload, None).await;
+    }
+
+    /// Report a backup that has not started, `queued` while it waits for a
+    /// free executor slot or `cancelled` before it got one
+    async fn report_pending(
+        &self,
+        generated_id: &str,
+        method: BackupMethod,
+        job_id: &str,
+        status: &str,
+    ) {
+        info!("[BackupService] DB: {} Status: {}", generated_id, status);
+
+        let payload = BackupPayload {
+            generated_id: generated_id.to_string(),
+            status: status.into(),
+            method: method.to_string(),
+            artifact: None,
+            timestamp: Utc::now().timestamp(),
+            objects: Vec::new(),
+            deferred_until: None,
+            job_id: Some(job_id.to_string()),
+        };
+        self.queue(payload, None).await;
+    }
+
+    async fn queue(&self, payload: BackupPayload, artifact: Option<&Path>) {
+        match Outbox::enqueue(OutboxPayload::Backup(Box::new(payload)), artifact).await {
+            Ok(id) => {
+                Outbox::deliver(&self.ctx, &id).await;
+            }
+            Err(e) => error!("Failed to queue backup result: {}", e),
+        }
+    }
+
+    /// Encrypt a dump into `dest` with a fresh AES key, itself sealed with the
+    /// server public key. Returns the size of the artifact.
+    async fn encrypt(
+        &self,
+        file_path: &Path,
+        dest: &Path,
+        result: &BackupResult,
+    ) -> Result<(u64, ArtifactInfo)> {
+        // AES key + IV
+        let mut aes_key = [0u8; 32];
+        rand_bytes(&mut aes_key)?;
+
+        let mut iv = [0u8; 16];
+        rand_bytes(&mut iv)?;
+
+        // AES CBC PKCS7 encryption, streamed from the dump to the outbox
+        let (src, dst) = (file_path.to_path_buf(), dest.to_path_buf());
+        let size = tokio::task::spawn_blocking(move || {
+            crypto::aes_file(Mode::Encrypt, &aes_key, &iv, &src, &dst)
+        })
+        .await??;
+
+        // Encrypt AES key with RSA public key
+        let pub_key_pem = self.ctx.edge_key.public_key.as_bytes();
+        let pkey = PKey::public_key_from_pem(pub_key_pem)?;
+        let encrypted_key = crypto::seal(&pkey, &aes_key)?;
+
+        // Verified databases are read back by the agent, which cannot open the server's seal
+        let agent_key = match &result.inventory {
+            Some(_) => Some(hex::encode(crypto::seal(crypto::agent_key()?, &aes_key)?)),
+            None => None,
+        };
+
+        let artifact = ArtifactInfo {
+            aes_key: hex::encode(encrypted_key),
+            iv: hex::encode(iv),
+            extension: full_extension(file_path),
+            filtered: Some(result.filtered),
+            agent_key,
+            inventory: result.inventory.clone(),
+        };
+
+        Ok((size, artifact))
+    }
+
+    /// Write the artifact to one storage target, unless a previous attempt already did
+    async fn store(
+        ctx: &Context,
+        payload: &mut BackupPayload,
+        info: &ArtifactInfo,
+        artifact: &Path,
+        target: &StorageConfig,
+    ) -> Result<(), DeliveryError> {
+        let stored = match target {
+            StorageConfig::Portabase => return Ok(()),
+            StorageConfig::S3(s3) => {
+                let key = object_key(
+                    &s3.prefix,
+                    &payload.generated_id,
+                    payload.timestamp,
+                    &info.extension,
+                );
+                if payload.objects.iter().any(|o| o.kind == "s3" && o.key == key) {
+                    return Ok(());
+                }
+                S3Storage::new(s3)
+                    .put(ctx, payload, info, artifact, &key)
+                    .await
+            }
+            StorageConfig::Local(local) => {
+                let key = object_key("", &payload.generated_id, payload.timestamp, &info.extension);
+                if payload
+                    .objects
+                    .iter()
+                    .any(|o| o.kind == "local" && o.location == local.path && o.key == key)
+                {
+                    return Ok(());
+                }
+                LocalStorage::new(local)
+                    .put(payload, info, artifact, &key)
+                    .await
+            }
+            StorageConfig::Sftp(sftp) => {
+                let storage = SftpStorage::new(sftp);
+                let key = format!(
+                    "{}/{}",
+                    storage.remote_dir(&payload.generated_id, payload.timestamp),
+                    artifact_name(payload.timestamp, &info.extension)
+                );
+                let location = storage.location();
+                if payload
+                    .objects
+                    .iter()
+                    .any(|o| o.kind == "sftp" && o.location == location && o.key == key)
+                {
+                    return Ok(());
+                }
+                storage.put(payload, info, artifact, &key).await
+            }
+        };
+
+        match stored {
+            Ok(object) => {
+                if let Ok(meta) = fs::metadata(artifact).await {
+                    metrics::record_upload(&object.kind, meta.len());
+                }
+                payload.objects.push(object);
+                Ok(())
+            }
+            Err(e) => {
+                error!("Failed to store backup artifact: {}", e);
+                Err(DeliveryError::Transient(e.to_string()))
+            }
+        }
+    }
+
+    /// Post a queued backup result, with its encrypted artifact when there is one
+    pub async fn deliver(
+        ctx: &Arc<Context>,
+        payload: &mut BackupPayload,
+        artifact: Option<&Path>,
+    ) -> Result<(), DeliveryError> {
         let client = reqwest::Client::new();
         let url = format!(
             "{}/api/agent/{}/backup",
-            self.ctx.edge_key.server_url, self.ctx.edge_key.agent_id
+            ctx.edge_key.server_url, ctx.edge_key.agent_id
         );
 
-        let mut form = Form::new()
-            .text("generatedId", result.generated_id.clone())
-            .text("status", result.status.clone())
-            .text("method", method.to_string());
-
-        if let Some(file_path) = result.backup_file {
-            match fs::read(&file_path).await {
-                Ok(raw_data) => {
-                    // AES key + IV
-                    let mut aes_key = [0u8; 32];
-                    rand_bytes(&mut aes_key).unwrap();
-
-                    let mut iv = [0u8; 16];
-                    rand_bytes(&mut iv).unwrap();
-
-                    // AES CBC PKCS7 encryption
-                    let cipher = Cipher::aes_256_cbc();
-                    let mut encrypter =
-                        Crypter::new(cipher, Mode::Encrypt, &aes_key, Some(&iv)).unwrap();
-                    encrypter.pad(true);
-                    let mut encrypted = vec![0u8; raw_data.len() + cipher.block_size()];
-                    let count = encrypter.update(&raw_data, &mut encrypted).unwrap();
-                    let rest = encrypter.finalize(&mut encrypted[count..]).unwrap();
-                    encrypted.truncate(count + rest);
-
-                    // Encrypt AES key with RSA public key
-                    let pub_key_pem = self.ctx.edge_key.public_key.as_bytes();
-                    let pkey = PKey::public_key_from_pem(pub_key_pem).unwrap();
-
-                    let mut encrypter = Encrypter::new(&pkey).unwrap();
-                    // Set OAEP padding (default OAEP uses SHA1, so override)
-                    encrypter.set_rsa_padding(Padding::PKCS1_OAEP).unwrap();
-                    // Set OAEP hash to SHA‑256
-                    encrypter.set_rsa_oaep_md(MessageDigest::sha256()).unwrap();
-                    encrypter.set_rsa_mgf1_md(MessageDigest::sha256()).unwrap();
-
-                    let mut encrypted_key = vec![0u8; encrypter.encrypt_len(&aes_key).unwrap()];
-                    let encrypted_len = encrypter.encrypt(&aes_key, &mut encrypted_key).unwrap();
-                    encrypted_key.truncate(encrypted_len);
-
-                    let extension = full_extension(&file_path);
-
-                    // Attach file and AES info to multipart form
-                    form = form
-                        .part(
-                            "file",
-                            Part::bytes(encrypted)
-                                .file_name(format!("{}.enc", result.generated_id)),
-                        )
-                        .text("aes_key", hex::encode(encrypted_key))
-                        .text("iv", hex::encode(iv))
-                        .text("extension", extension);
+        let mut upload_to_server = true;
+        let mut cancelled = false;
+        if let (Some(info), Some(path)) = (payload.artifact.clone(), artifact) {
+            let config = ConfigService::new(ctx.clone())
+                .current()
+                .map_err(DeliveryError::Transient)?;
+            let targets = config.storage_targets();
+            upload_to_server = targets
+                .iter()
+                .any(|t| matches!(t, StorageConfig::Portabase));
+
+            for target in targets.iter() {
+                if cancel_requested() {
+                    cancelled = true;
+                    break;
                 }
-                Err(e) => {
-                    error!("Failed to read backup file: {}", e);
+                Self::store(ctx, payload, &info, path, target).await?;
+            }
+
+            if upload_to_server && !cancelled {
+                match ChunkedUpload::new(ctx, payload, &info, path).run().await? {
+                    UploadOutcome::Completed => {
+                        info!("Backup result sent successfully");
+                        return Ok(());
+                    }
+                    UploadOutcome::Unsupported => {
+                        info!("Chunked upload unsupported by server, sending backup in a single request")
+                    }
+                    UploadOutcome::Cancelled => cancelled = true,
                 }
             }
-        } else {
-            form = form.text("file", "");
         }
 
-        match client.post(&url).multipart(form).send().await {
-            Ok(resp) => {
-                let status = resp.status();
-                if status.is_success() {
-                    info!("Backup result sent successfully");
-                } else {
-                    let text = resp.text().await.unwrap_or_default(); // consumes resp
-                    error!("Backup result failed, status: {}, body: {}", status, text);
+        // The artifact is dropped with the entry, only the cancellation is reported
+        if cancelled {
+            info!(
+                "[BackupService] DB: {} upload stopped, job cancelled",
+                payload.generated_id
+            );
+            payload.status = "cancelled".into();
+            payload.artifact = None;
+        }
+
+        let mut form = Form::new()
+            .text("generatedId", payload.generated_id.clone())
+            .text("status", payload.status.clone())
+            .text("method", payload.method.clone());
+
+        if let Some(job_id) = &payload.job_id {
+            form = form.text("jobId", job_id.clone());
+        }
+
+        if let Some(until) = payload.deferred_until {
+            form = form.text("deferredUntil", until.to_string());
+        }
+
+        if !payload.objects.is_empty() {
+            let storage = serde_json::to_string(&payload.objects)
+                .map_err(|e| DeliveryError::Transient(e.to_string()))?;
+            form = form.text("storage", storage);
+        }
+
+        match (&payload.artifact, artifact) {
+            (Some(info), Some(path)) => {
+                if upload_to_server {
+                    let file = fs::File::open(path).await?;
+                    let size = file.metadata().await?.len();
+                    metrics::record_upload("portabase", size);
+
+                    // Attach file to multipart form, streamed from the outbox
+                    form = form.part(
+                        "file",
+                        Part::stream_with_length(Body::from(file), size)
+                            .file_name(format!("{}.enc", payload.generated_id)),
+                    );
+                }
+
+                // Attach AES info to multipart form
+                form = form
+                    .text("aes_key", info.aes_key.clone())
+                    .text("iv", info.iv.clone())
+                    .text("extension", info.extension.clone());
+                if let Some(filtered) = info.filtered {
+                    form = form.text("filtered", filtered.to_string());
                 }
             }
-            Err(e) => {
-                error!("Failed to send backup result: {}", e);
+            _ => {
+                form = form.text("file", "");
             }
         }
+
+        let resp = client.post(&url).multipart(form).send().await?;
+        let status = resp.status();
+        if status.is_success() {
+            info!("Backup result sent successfully");
+            Ok(())
+        } else {
+            let text = resp.text().await.unwrap_or_default(); // consumes resp
+            error!("Backup result failed, status: {}, body: {}", status, text);
+            Err(DeliveryError::from_response(status, text))
+        }
     }
 }
@@ -1,16 +1,30 @@
 #![allow(dead_code)]
 
 use crate::core::context::Context;
+use crate::services::discovery;
+use crate::services::options::{
+    DatabaseOptions, Hooks, MongoOptions, MysqlOptions, PostgresOptions,
+};
+use crate::settings::CONFIG;
+use crate::utils::blackout::{self, ActiveBlackout};
+use crate::utils::secret::{Secret, interpolate, read_secret_file};
+use crate::utils::task_manager::cron::next_run_timestamp;
+use crate::utils::task_manager::models::MisfirePolicy;
+use crate::utils::text::{glob_match, normalize_cron};
+use once_cell::sync::Lazy;
 use serde::Deserialize;
-use serde_json;
+use serde_json::{self, Value};
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use toml;
-use tracing::info;
+use tracing::{error, info};
+use uuid::Uuid;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum DbType {
     Mysql,
@@ -34,25 +48,561 @@ impl DbType {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
 pub struct DatabaseConfig {
     pub name: String,
     pub database: String,
     #[serde(rename = "type")]
     pub db_type: DbType,
+    #[serde(default)]
+    pub username: String,
+    /// May reference `${ENV_VAR}`s, resolved when the config is loaded
+    #[serde(default)]
+    pub password: Secret,
+    /// File holding the password, such as a Docker or Kubernetes secret
+    #[serde(default)]
+    pub password_file: Option<String>,
+    /// Secret store the credentials are fetched from before each operation,
+    /// instead of `username` and `password`
+    #[serde(default)]
+    pub credentials: Option<CredentialsConfig>,
+    pub port: u16,
+    pub host: String,
+    pub generated_id: String,
+    /// Marks this entry as a scratch target that verification restores into.
+    /// Sandboxes are never reported to the server nor backed up.
+    #[serde(default)]
+    pub sandbox: bool,
+    #[serde(default)]
+    pub verification: Option<VerificationConfig>,
+    /// Applied to this database's scheduled tasks when the agent missed their time
+    #[serde(default)]
+    pub misfire: MisfirePolicy,
+    /// Periods during which this database must not be dumped, on top of the global ones
+    #[serde(default)]
+    pub blackouts: Vec<BlackoutWindow>,
+    #[serde(default)]
+    pub timeouts: TimeoutsConfig,
+    /// Engine-specific `options`, checked against `type` when the config is loaded
+    #[serde(default, rename = "options")]
+    raw_options: Option<Value>,
+    #[serde(skip)]
+    pub options: Option<DatabaseOptions>,
+    /// Name of the server entry this database was discovered on
+    #[serde(skip)]
+    pub discovered_from: Option<String>,
+}
+
+/// Database server whose databases are discovered on every ping rather than
+/// listed one by one. Each database gets the `generated_id` UUIDv5 of its name
+/// in the namespace of the server's `generated_id`, stable across agents.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub db_type: DbType,
+    #[serde(default)]
     pub username: String,
-    pub password: String,
+    #[serde(default)]
+    pub password: Secret,
+    #[serde(default)]
+    pub password_file: Option<String>,
+    #[serde(default)]
+    pub credentials: Option<CredentialsConfig>,
     pub port: u16,
     pub host: String,
+    /// UUID namespace of the ids of the discovered databases
     pub generated_id: String,
+    /// Glob patterns (`*`, `?`) of database names to back up, all when empty
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Glob patterns of database names to leave out, on top of system databases
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub misfire: MisfirePolicy,
+    #[serde(default)]
+    pub blackouts: Vec<BlackoutWindow>,
+    #[serde(default)]
+    pub timeouts: TimeoutsConfig,
+    /// `options` given to each discovered database
+    #[serde(default, rename = "options")]
+    raw_options: Option<Value>,
+    #[serde(skip)]
+    pub options: Option<DatabaseOptions>,
+}
+
+impl ServerConfig {
+    fn resolve_password(&mut self) -> Result<(), String> {
+        resolve_password(
+            &mut self.password,
+            &mut self.password_file,
+            self.credentials.as_ref(),
+        )
+    }
+
+    fn namespace(&self) -> Result<Uuid, String> {
+        Uuid::parse_str(&self.generated_id)
+            .map_err(|e| format!("generated_id must be a UUID: {}", e))
+    }
+
+    /// Whether a database discovered on this server is backed up
+    pub fn selects(&self, database: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| glob_match(p, database)))
+            && !self.exclude.iter().any(|p| glob_match(p, database))
+    }
+
+    /// Entry of one of the server's databases
+    pub fn database(&self, database: &str) -> DatabaseConfig {
+        let namespace = self.namespace().unwrap_or(Uuid::nil());
+        DatabaseConfig {
+            name: format!("{} - {}", self.name, database),
+            database: database.to_string(),
+            db_type: self.db_type.clone(),
+            username: self.username.clone(),
+            password: self.password.clone(),
+            password_file: None,
+            credentials: self.credentials.clone(),
+            port: self.port,
+            host: self.host.clone(),
+            generated_id: Uuid::new_v5(&namespace, database.as_bytes()).to_string(),
+            sandbox: false,
+            verification: None,
+            misfire: self.misfire,
+            blackouts: self.blackouts.clone(),
+            timeouts: self.timeouts.clone(),
+            raw_options: self.raw_options.clone(),
+            options: self.options.clone(),
+            discovered_from: Some(self.name.clone()),
+        }
+    }
+}
+
+/// Limits in seconds for the external commands of an operation, `0` disables
+/// a limit and unset ones fall back to `BACKUP_TIMEOUT_SECONDS`, `RESTORE_TIMEOUT_SECONDS`
+/// and `QUERY_TIMEOUT_SECONDS`
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TimeoutsConfig {
+    pub backup: Option<u64>,
+    pub restore: Option<u64>,
+    /// Short queries: pings, server version, database listing
+    pub query: Option<u64>,
+}
+
+/// Replace `password` by its value from `password_file` or the environment
+/// variables it references, checking it does not compete with `credentials`
+fn resolve_password(
+    password: &mut Secret,
+    password_file: &mut Option<String>,
+    credentials: Option<&CredentialsConfig>,
+) -> Result<(), String> {
+    match credentials {
+        Some(_) if !password.is_empty() || password_file.is_some() => {
+            return Err("set either password or credentials, not both".into());
+        }
+        Some(CredentialsConfig::Exec(exec)) if exec.command.is_empty() => {
+            return Err("credentials command is empty".into());
+        }
+        _ => {}
+    }
+    let resolved = match password_file.take() {
+        Some(_) if !password.is_empty() => {
+            return Err("set either password or password_file, not both".into());
+        }
+        Some(path) => read_secret_file(&interpolate(&path)?)?,
+        None => interpolate(password.expose())?,
+    };
+    *password = Secret::new(resolved);
+    Ok(())
+}
+
+/// Timeout of `seconds`, none when 0
+pub fn limit(seconds: u64) -> Option<Duration> {
+    (seconds > 0).then(|| Duration::from_secs(seconds))
+}
+
+impl DatabaseConfig {
+    /// Replace the password by its resolved value
+    fn resolve_password(&mut self) -> Result<(), String> {
+        resolve_password(
+            &mut self.password,
+            &mut self.password_file,
+            self.credentials.as_ref(),
+        )
+    }
+
+    /// Parse `options` for the database's engine
+    fn resolve_options(&mut self) -> Result<(), String> {
+        self.options = Some(DatabaseOptions::parse(&self.db_type, self.raw_options.as_ref())?);
+        Ok(())
+    }
+
+    pub fn postgres_options(&self) -> PostgresOptions {
+        match &self.options {
+            Some(DatabaseOptions::Postgres(options)) => options.clone(),
+            _ => PostgresOptions::default(),
+        }
+    }
+
+    pub fn mysql_options(&self) -> MysqlOptions {
+        match &self.options {
+            Some(DatabaseOptions::Mysql(options)) => options.clone(),
+            _ => MysqlOptions::default(),
+        }
+    }
+
+    pub fn mongo_options(&self) -> MongoOptions {
+        match &self.options {
+            Some(DatabaseOptions::Mongo(options)) => options.clone(),
+            _ => MongoOptions::default(),
+        }
+    }
+
+    pub fn hooks(&self) -> Hooks {
+        self.options.as_ref().map(|o| o.hooks().clone()).unwrap_or_default()
+    }
+
+    /// Whether dumps cover part of the database only, recorded with each
+    /// artifact so that restoring it keeps what the dump leaves out
+    pub fn is_filtered(&self) -> bool {
+        self.options.as_ref().is_some_and(DatabaseOptions::is_filtered)
+    }
+
+    pub fn backup_timeout(&self) -> Option<Duration> {
+        limit(self.timeouts.backup.unwrap_or(CONFIG.backup_timeout))
+    }
+
+    pub fn restore_timeout(&self) -> Option<Duration> {
+        limit(self.timeouts.restore.unwrap_or(CONFIG.restore_timeout))
+    }
+
+    pub fn query_timeout(&self) -> Option<Duration> {
+        limit(self.timeouts.query.unwrap_or(CONFIG.query_timeout))
+    }
+}
+
+/// Provider of database credentials
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum CredentialsConfig {
+    Vault(VaultConfig),
+    Exec(ExecConfig),
+}
+
+/// HashiCorp Vault secret, read with `token`, `token_file` or `VAULT_TOKEN`
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct VaultConfig {
+    pub address: String,
+    /// May reference `${ENV_VAR}`s, resolved when the credentials are fetched
+    #[serde(default)]
+    pub token: Secret,
+    #[serde(default)]
+    pub token_file: Option<String>,
+    #[serde(default)]
+    pub namespace: Option<String>,
+    #[serde(default)]
+    pub engine: VaultEngine,
+    /// Mount point of the secrets engine
+    pub mount: String,
+    /// Secret path for KV engines, role name for the database engine
+    pub path: String,
+    #[serde(default = "default_username_key")]
+    pub username_key: String,
+    #[serde(default = "default_password_key")]
+    pub password_key: String,
+}
+
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VaultEngine {
+    #[default]
+    Kv2,
+    Kv1,
+    /// Dynamic credentials issued for the role at `path`
+    Database,
+}
+
+fn default_username_key() -> String {
+    "username".into()
+}
+
+fn default_password_key() -> String {
+    "password".into()
+}
+
+/// Command printing `{"username": "...", "password": "..."}` on stdout
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ExecConfig {
+    /// Program followed by its arguments
+    pub command: Vec<String>,
+    /// Seconds before the command is killed, 0 for no limit
+    #[serde(default = "default_exec_timeout")]
+    pub timeout: u64,
+}
+
+fn default_exec_timeout() -> u64 {
+    30
+}
+
+/// Scheduled restore verification of a database into a sandbox
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct VerificationConfig {
+    /// `generated_id` of the sandbox entry to restore into
+    pub sandbox: String,
+    pub cron: String,
+    /// IANA timezone of `cron`, the agent's local time when unset
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// Accepted difference between the row counts recorded after the dump
+    /// and the restored ones, in percent, for writes that raced the dump
+    #[serde(default = "default_verification_tolerance")]
+    pub tolerance: f64,
+}
+
+fn default_verification_tolerance() -> f64 {
+    1.0
+}
+
+/// Period during which backups must not touch a database
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(try_from = "RawBlackoutWindow")]
+pub struct BlackoutWindow {
+    pub period: BlackoutPeriod,
+    /// IANA timezone of the period, the agent's local time when unset
+    pub timezone: Option<String>,
+    pub action: BlackoutAction,
+}
+
+/// Blackout window as written in the file, the period keys side by side
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawBlackoutWindow {
+    cron: Option<String>,
+    duration: Option<u64>,
+    from: Option<String>,
+    to: Option<String>,
+    #[serde(default)]
+    timezone: Option<String>,
+    #[serde(default)]
+    action: BlackoutAction,
+}
+
+impl TryFrom<RawBlackoutWindow> for BlackoutWindow {
+    type Error = String;
+
+    fn try_from(raw: RawBlackoutWindow) -> Result<Self, String> {
+        let period = match (raw.cron, raw.duration, raw.from, raw.to) {
+            (Some(cron), Some(duration), None, None) => BlackoutPeriod::Cron { cron, duration },
+            (None, None, Some(from), Some(to)) => BlackoutPeriod::Range { from, to },
+            _ => {
+                return Err(
+                    "blackout window needs either `cron` and `duration`, or `from` and `to`".into(),
+                );
+            }
+        };
+        Ok(BlackoutWindow {
+            period,
+            timezone: raw.timezone,
+            action: raw.action,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlackoutPeriod {
+    /// Opens at each occurrence of `cron` and stays open for `duration` seconds
+    Cron { cron: String, duration: u64 },
+    /// Daily range of wall-clock times (`HH:MM`), may span midnight
+    Range { from: String, to: String },
+}
+
+/// What happens to a scheduled run that falls inside a blackout window
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BlackoutAction {
+    /// Run once when the window closes
+    #[default]
+    Defer,
+    /// Drop the run and wait for the next occurrence
+    Skip,
+}
+
+/// Destination of encrypted backup artifacts
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum StorageConfig {
+    /// Upload to the Portabase server, the default when no storage is configured
+    Portabase,
+    S3(S3Config),
+    Local(LocalConfig),
+    Sftp(SftpConfig),
+}
+
+/// S3-compatible bucket. Without credentials, uploads go through
+/// pre-signed URLs issued by the server.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct S3Config {
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    #[serde(default)]
+    pub prefix: String,
+    pub access_key: Option<String>,
+    pub secret_key: Option<Secret>,
+    pub session_token: Option<Secret>,
+    /// Address the bucket as `endpoint/bucket` rather than `bucket.endpoint`
+    #[serde(default = "default_true")]
+    pub path_style: bool,
+}
+
+/// Directory (NFS mount, external disk...) receiving artifacts and their manifests
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LocalConfig {
+    pub path: String,
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+}
+
+/// SFTP server authenticated with a private key
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SftpConfig {
+    pub host: String,
+    #[serde(default = "default_sftp_port")]
+    pub port: u16,
+    pub username: String,
+    pub private_key: String,
+    pub passphrase: Option<Secret>,
+    /// OpenSSH `known_hosts` file used to verify the server key, required
+    /// unless `insecure_skip_host_key_check` is set
+    pub known_hosts: Option<String>,
+    /// Accept any server key when no `known_hosts` is given
+    #[serde(default)]
+    pub insecure_skip_host_key_check: bool,
+    /// Remote directory, `{generated_id}`, `{year}`, `{month}` and `{day}` are substituted
+    #[serde(default = "default_sftp_directory")]
+    pub directory: String,
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+}
+
+fn default_sftp_port() -> u16 {
+    22
+}
+
+fn default_sftp_directory() -> String {
+    "backups/{generated_id}".into()
+}
+
+/// Copies to keep per database; a copy is kept when any rule selects it.
+/// Nothing is pruned when every rule is zero.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct RetentionPolicy {
+    #[serde(default)]
+    pub keep_last: usize,
+    #[serde(default)]
+    pub keep_daily: usize,
+    #[serde(default)]
+    pub keep_weekly: usize,
+    #[serde(default)]
+    pub keep_monthly: usize,
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".into()
+}
+
+fn default_true() -> bool {
+    true
 }
 
 #[allow(dead_code)]
 #[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct DatabasesConfig {
     pub databases: Vec<DatabaseConfig>,
+    #[serde(default)]
+    pub storage: Vec<StorageConfig>,
+    /// Blackout windows applying to every database
+    #[serde(default)]
+    pub blackouts: Vec<BlackoutWindow>,
+    #[serde(default)]
+    pub servers: Vec<ServerConfig>,
+}
+
+impl DatabasesConfig {
+    pub fn find(&self, generated_id: &str) -> Option<&DatabaseConfig> {
+        self.databases.iter().find(|c| c.generated_id == generated_id)
+    }
+
+    /// Configured storage targets, the Portabase server when none is set
+    pub fn storage_targets(&self) -> Vec<StorageConfig> {
+        if self.storage.is_empty() {
+            vec![StorageConfig::Portabase]
+        } else {
+            self.storage.clone()
+        }
+    }
+
+    /// Blackout in force for a database at `at`, from global or database windows
+    pub fn blackout(&self, generated_id: &str, at: i64) -> Option<ActiveBlackout> {
+        let own = self.find(generated_id).map(|c| c.blackouts.as_slice()).unwrap_or_default();
+        blackout::active(self.blackouts.iter().chain(own), at)
+    }
+
+    /// Databases handled by the agent, excluding verification sandboxes
+    pub fn managed(&self) -> impl Iterator<Item = &DatabaseConfig> {
+        self.databases.iter().filter(|c| !c.sandbox)
+    }
+}
+
+/// Databases added, changed and removed by a config reload, by `generated_id`
+#[derive(Debug, Default)]
+pub struct ConfigDiff {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
 }
 
+impl ConfigDiff {
+    fn between(old: &[&DatabaseConfig], new: &[&DatabaseConfig]) -> Self {
+        fn find<'a>(list: &[&'a DatabaseConfig], id: &str) -> Option<&'a DatabaseConfig> {
+            list.iter().find(|db| db.generated_id == id).copied()
+        }
+        let mut diff = ConfigDiff::default();
+        for db in new.iter() {
+            match find(old, &db.generated_id) {
+                None => diff.added.push(db.generated_id.clone()),
+                Some(previous) if previous != *db => diff.changed.push(db.generated_id.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.removed = old
+            .iter()
+            .filter(|db| find(new, &db.generated_id).is_none())
+            .map(|db| db.generated_id.clone())
+            .collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Last config that loaded and validated, kept while the file is broken
+static ACTIVE: Lazy<RwLock<Option<Arc<DatabasesConfig>>>> = Lazy::new(|| RwLock::new(None));
+
 pub struct ConfigService {
     ctx: Arc<Context>,
 }
@@ -62,15 +612,128 @@ impl ConfigService {
         ConfigService { ctx }
     }
 
-    pub fn load(&self, file_path: Option<&str>) -> Result<DatabasesConfig, String> {
-        let path: String = if let Some(fp) = file_path {
-            fp.to_string()
-        } else {
-            format!(
-                "{}/{}",
-                crate::settings::CONFIG.data_path,
-                crate::settings::CONFIG.databases_config_file
-            )
+    /// Path of the databases config file
+    pub fn path() -> String {
+        format!(
+            "{}/{}",
+            crate::settings::CONFIG.data_path,
+            crate::settings::CONFIG.databases_config_file
+        )
+    }
+
+    /// Config in use, loading the file on first use
+    pub fn current(&self) -> Result<Arc<DatabasesConfig>, String> {
+        if let Some(config) = ACTIVE.read().unwrap().as_ref() {
+            return Ok(config.clone());
+        }
+        let mut active = ACTIVE.write().unwrap();
+        // Loaded by another caller while this one waited for the lock
+        if let Some(config) = active.as_ref() {
+            return Ok(config.clone());
+        }
+        Self::swap_in(&mut active).map(|(config, _)| config)
+    }
+
+    /// Load and validate the file, and swap it in when valid. On error the
+    /// previous config stays in use.
+    pub fn reload(&self) -> Result<(Arc<DatabasesConfig>, ConfigDiff), String> {
+        Self::swap_in(&mut ACTIVE.write().unwrap())
+    }
+
+    /// Body of `reload`, with the lock held so that loads never overlap
+    fn swap_in(
+        active: &mut Option<Arc<DatabasesConfig>>,
+    ) -> Result<(Arc<DatabasesConfig>, ConfigDiff), String> {
+        let mut config = Self::load(None)?;
+        let diff = match active.as_ref() {
+            Some(previous) => {
+                let listed = |c: &DatabasesConfig| {
+                    c.databases
+                        .iter()
+                        .filter(|db| db.discovered_from.is_none())
+                        .cloned()
+                        .collect::<Vec<_>>()
+                };
+                let mut diff = ConfigDiff::between(
+                    &listed(previous).iter().collect::<Vec<_>>(),
+                    &config.databases.iter().collect::<Vec<_>>(),
+                );
+                // Databases of servers still configured stay known until the next
+                // discovery, those of removed servers go with them
+                let (kept, dropped): (Vec<_>, Vec<_>) = previous
+                    .databases
+                    .iter()
+                    .filter_map(|db| Some((db.discovered_from.as_ref()?, db)))
+                    .filter(|(_, db)| config.find(&db.generated_id).is_none())
+                    .partition(|(server, _)| config.servers.iter().any(|s| &&s.name == server));
+                diff.removed
+                    .extend(dropped.into_iter().map(|(_, db)| db.generated_id.clone()));
+                config.databases.extend(kept.into_iter().map(|(_, db)| db.clone()));
+                diff
+            }
+            None => ConfigDiff::default(),
+        };
+        let config = Arc::new(config);
+        *active = Some(config.clone());
+        Ok((config, diff))
+    }
+
+    /// Enumerate the databases of every server entry and swap them into the
+    /// current config. A server that cannot be listed keeps its previous databases.
+    pub async fn discover(&self) -> Result<(Arc<DatabasesConfig>, ConfigDiff), String> {
+        let current = self.current()?;
+        if current.servers.is_empty() {
+            return Ok((current, ConfigDiff::default()));
+        }
+
+        let mut config = (*current).clone();
+        config.databases.retain(|db| db.discovered_from.is_none());
+        for server in current.servers.iter() {
+            let found = match discovery::discover(server).await {
+                Ok(found) => found,
+                Err(e) => {
+                    error!("Failed to discover databases on {}: {:#}", server.name, e);
+                    current
+                        .databases
+                        .iter()
+                        .filter(|db| db.discovered_from.as_ref() == Some(&server.name))
+                        .cloned()
+                        .collect()
+                }
+            };
+            for db in found {
+                if config.find(&db.generated_id).is_some() {
+                    continue;
+                }
+                config.databases.push(db);
+            }
+        }
+
+        let discovered = |c: &DatabasesConfig| -> Vec<DatabaseConfig> {
+            c.databases
+                .iter()
+                .filter(|db| db.discovered_from.is_some())
+                .cloned()
+                .collect()
+        };
+        let mut active = ACTIVE.write().unwrap();
+        // The file was reloaded meanwhile, its own discovery comes with the next ping
+        if !active.as_ref().is_some_and(|a| Arc::ptr_eq(a, &current)) {
+            return Ok((current, ConfigDiff::default()));
+        }
+        let diff = ConfigDiff::between(
+            &discovered(&current).iter().collect::<Vec<_>>(),
+            &discovered(&config).iter().collect::<Vec<_>>(),
+        );
+        let config = Arc::new(config);
+        *active = Some(config.clone());
+        Ok((config, diff))
+    }
+
+    pub fn load(file_path: Option<&str>) -> Result<DatabasesConfig, String> {
+        let path: String = match file_path {
+            Some(fp) => fp.to_string(),
+            None => Self::path(),
         };
 
         info!("Loading databases config from: {}", path);
@@ -95,16 +758,91 @@ impl ConfigService {
         file.read_to_string(&mut contents)
             .map_err(|e| format!("Failed to read config file: {}", e))?;
 
-        let config: DatabasesConfig = match extension {
+        let mut config: DatabasesConfig = match extension {
             "json" => {
                 serde_json::from_str(&contents).map_err(|e| format!("JSON parsing error: {}", e))?
             }
             "toml" => {
                 toml::from_str(&contents).map_err(|e| format!("TOML parsing error: {}", e))?
             }
-            _ => return Err("Unsupported config file format. Use .json or .toml".to_string()),
+            "yaml" | "yml" => serde_yaml::from_str(&contents)
+                .map_err(|e| format!("YAML parsing error: {}", e))?,
+            _ => {
+                return Err("Unsupported config file format. Use .json, .toml or .yaml".to_string());
+            }
         };
 
+        for db in config.databases.iter_mut() {
+            db.resolve_password()
+                .map_err(|e| format!("Invalid password for {}: {}", db.generated_id, e))?;
+            db.resolve_options()
+                .map_err(|e| format!("Invalid options for {}: {}", db.generated_id, e))?;
+        }
+
+        let mut server_names = HashSet::new();
+        for server in config.servers.iter_mut() {
+            if !server_names.insert(server.name.clone()) {
+                return Err(format!("Duplicate server name: {}", server.name));
+            }
+            server
+                .namespace()
+                .map_err(|e| format!("Invalid server {}: {}", server.name, e))?;
+            server
+                .resolve_password()
+                .map_err(|e| format!("Invalid password for server {}: {}", server.name, e))?;
+            server.options = Some(
+                DatabaseOptions::parse(&server.db_type, server.raw_options.as_ref())
+                    .map_err(|e| format!("Invalid options for server {}: {}", server.name, e))?,
+            );
+        }
+
+        for window in config
+            .blackouts
+            .iter()
+            .chain(config.databases.iter().flat_map(|c| c.blackouts.iter()))
+            .chain(config.servers.iter().flat_map(|s| s.blackouts.iter()))
+        {
+            window
+                .validate()
+                .map_err(|e| format!("Invalid blackout window: {}", e))?;
+        }
+
+        let mut seen = HashSet::new();
+        for db in config.databases.iter() {
+            if !seen.insert(db.generated_id.as_str()) {
+                return Err(format!("Duplicate generated_id: {}", db.generated_id));
+            }
+        }
+
+        for db in config.databases.iter() {
+            let Some(verification) = &db.verification else {
+                continue;
+            };
+            if !config
+                .find(&verification.sandbox)
+                .is_some_and(|sandbox| sandbox.sandbox)
+            {
+                return Err(format!(
+                    "Invalid verification for {}: sandbox {} is not a sandbox entry",
+                    db.generated_id, verification.sandbox
+                ));
+            }
+            next_run_timestamp(&normalize_cron(&verification.cron), verification.timezone.as_deref())
+                .map_err(|e| format!("Invalid verification for {}: {}", db.generated_id, e))?;
+        }
+
+        for target in config.storage.iter() {
+            if let StorageConfig::Sftp(sftp) = target
+                && sftp.known_hosts.is_none()
+                && !sftp.insecure_skip_host_key_check
+            {
+                return Err(format!(
+                    "Invalid SFTP storage {}: known_hosts is required unless insecure_skip_host_key_check is set",
+                    sftp.host
+                ));
+            }
+        }
+
         info!("Databases : {:?} instances loaded", config.databases.len());
 
         Ok(config)
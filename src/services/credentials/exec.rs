@@ -0,0 +1,61 @@
+use crate::services::config::{ExecConfig, limit};
+use crate::services::credentials::Credentials;
+use crate::utils::process::ProcessControl;
+use crate::utils::secret::Secret;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::process::Command;
+
+#[derive(Deserialize)]
+struct ExecOutput {
+    #[serde(default)]
+    username: Option<String>,
+    password: Secret,
+}
+
+pub struct ExecProvider<'a> {
+    cfg: &'a ExecConfig,
+}
+
+impl<'a> ExecProvider<'a> {
+    pub fn new(cfg: &'a ExecConfig) -> Self {
+        Self { cfg }
+    }
+
+    /// Run the command and parse its stdout, which is never logged
+    pub async fn fetch(&self) -> Result<Credentials> {
+        let Some((program, args)) = self.cfg.command.split_first() else {
+            anyhow::bail!("Credentials command is empty");
+        };
+        let mut cmd = Command::new(program);
+        cmd.args(args);
+
+        let control = ProcessControl::current(limit(self.cfg.timeout));
+        let output = tokio::task::spawn_blocking(move || control.output(&mut cmd))
+            .await?
+            .with_context(|| format!("Failed to run credentials command {}", program))?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "Credentials command {} failed with status {}",
+                program,
+                output.status
+            );
+        }
+
+        // Only the error position is reported, serde messages may quote the values
+        let parsed: ExecOutput = serde_json::from_slice(&output.stdout).map_err(|e| {
+            anyhow::anyhow!(
+                "Credentials command {} printed invalid JSON at line {} column {}",
+                program,
+                e.line(),
+                e.column()
+            )
+        })?;
+        Ok(Credentials {
+            username: parsed.username,
+            password: parsed.password,
+            lease: None,
+        })
+    }
+}
@@ -0,0 +1,103 @@
+pub mod exec;
+pub mod vault;
+
+use crate::services::config::{CredentialsConfig, DatabaseConfig, VaultConfig};
+use crate::utils::secret::Secret;
+use anyhow::{Context, Result};
+use exec::ExecProvider;
+use once_cell::sync::Lazy;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use vault::VaultProvider;
+
+/// Credentials handed out by a provider, kept in memory only
+#[derive(Clone)]
+pub struct Credentials {
+    /// Falls back to the configured username when the provider has none
+    pub username: Option<String>,
+    pub password: Secret,
+    /// Id and duration of the lease of dynamic credentials
+    pub lease: Option<(String, Duration)>,
+}
+
+/// Dynamic credentials in use. The lease is revoked once the cache let go of
+/// it and the last operation holding it is done.
+pub struct Lease {
+    vault: VaultConfig,
+    id: String,
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let vault = self.vault.clone();
+        let id = std::mem::take(&mut self.id);
+        handle.spawn(async move {
+            match VaultProvider::new(&vault).revoke(&id).await {
+                Ok(()) => debug!("Revoked Vault lease {}", id),
+                Err(e) => warn!("Failed to revoke Vault lease {}: {:#}", id, e),
+            }
+        });
+    }
+}
+
+struct CachedLease {
+    vault: VaultConfig,
+    credentials: Credentials,
+    lease: Arc<Lease>,
+}
+
+/// Dynamic credentials are reused until three quarters of their lease have
+/// elapsed, so that pings and discovery do not create a user every time
+static LEASES: Lazy<Mutex<Vec<CachedLease>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Fill in the credentials of `cfg` from its provider, when it has one.
+/// Called right before each backup or restore so that rotated or dynamic
+/// credentials are always current. Dynamic credentials stay valid as long
+/// as the returned lease is held.
+pub async fn resolve(mut cfg: DatabaseConfig) -> Result<(DatabaseConfig, Option<Arc<Lease>>)> {
+    let Some(provider) = cfg.credentials.take() else {
+        return Ok((cfg, None));
+    };
+
+    let (credentials, lease) = match &provider {
+        CredentialsConfig::Vault(vault) => fetch_vault(vault).await,
+        CredentialsConfig::Exec(exec) => ExecProvider::new(exec).fetch().await.map(|c| (c, None)),
+    }
+    .with_context(|| format!("Failed to fetch credentials for {}", cfg.name))?;
+
+    info!("Credentials fetched for {}", cfg.name);
+    if let Some(username) = credentials.username {
+        cfg.username = username;
+    }
+    cfg.password = credentials.password;
+    Ok((cfg, lease))
+}
+
+async fn fetch_vault(vault: &VaultConfig) -> Result<(Credentials, Option<Arc<Lease>>)> {
+    if let Some(cached) = LEASES.lock().unwrap().iter().find(|c| c.vault == *vault) {
+        return Ok((cached.credentials.clone(), Some(cached.lease.clone())));
+    }
+
+    let credentials = VaultProvider::new(vault).fetch().await?;
+    let Some((id, duration)) = credentials.lease.clone() else {
+        return Ok((credentials, None));
+    };
+    let lease = Arc::new(Lease {
+        vault: vault.clone(),
+        id: id.clone(),
+    });
+    LEASES.lock().unwrap().push(CachedLease {
+        vault: vault.clone(),
+        credentials: credentials.clone(),
+        lease: lease.clone(),
+    });
+    tokio::spawn(async move {
+        tokio::time::sleep(duration * 3 / 4).await;
+        LEASES.lock().unwrap().retain(|c| c.lease.id != id);
+    });
+    Ok((credentials, Some(lease)))
+}
@@ -0,0 +1,122 @@
+use crate::services::config::{VaultConfig, VaultEngine};
+use crate::services::credentials::Credentials;
+use crate::utils::secret::{Secret, interpolate, read_secret_file};
+use anyhow::{Context, Result};
+use reqwest::{Client, Method, RequestBuilder};
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use std::time::Duration;
+use tracing::debug;
+
+#[derive(Deserialize)]
+struct SecretResponse {
+    data: Map<String, Value>,
+    #[serde(default)]
+    lease_id: String,
+    #[serde(default)]
+    lease_duration: u64,
+}
+
+pub struct VaultProvider<'a> {
+    cfg: &'a VaultConfig,
+    client: Client,
+}
+
+impl<'a> VaultProvider<'a> {
+    pub fn new(cfg: &'a VaultConfig) -> Self {
+        Self {
+            cfg,
+            client: Client::new(),
+        }
+    }
+
+    fn token(&self) -> Result<String> {
+        let token = match &self.cfg.token_file {
+            Some(path) => read_secret_file(&interpolate(path).map_err(anyhow::Error::msg)?),
+            None if !self.cfg.token.is_empty() => interpolate(self.cfg.token.expose()),
+            None => std::env::var("VAULT_TOKEN")
+                .map_err(|_| "no token, token_file nor VAULT_TOKEN set".to_string()),
+        };
+        token.map_err(anyhow::Error::msg)
+    }
+
+    fn url(&self) -> String {
+        let address = self.cfg.address.trim_end_matches('/');
+        let mount = self.cfg.mount.trim_matches('/');
+        let path = self.cfg.path.trim_matches('/');
+        match self.cfg.engine {
+            VaultEngine::Kv2 => format!("{}/v1/{}/data/{}", address, mount, path),
+            VaultEngine::Kv1 => format!("{}/v1/{}/{}", address, mount, path),
+            VaultEngine::Database => format!("{}/v1/{}/creds/{}", address, mount, path),
+        }
+    }
+
+    fn request(&self, method: Method, url: &str) -> Result<RequestBuilder> {
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("X-Vault-Token", self.token()?);
+        if let Some(namespace) = &self.cfg.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+        Ok(request)
+    }
+
+    pub async fn fetch(&self) -> Result<Credentials> {
+        let url = self.url();
+        let resp = self.request(Method::GET, &url)?.send().await?;
+        let status = resp.status();
+        if !status.is_success() {
+            anyhow::bail!("Vault request to {} failed with status {}", url, status);
+        }
+        let secret: SecretResponse = resp.json().await?;
+
+        // KV v2 nests the secret under data.data, next to its metadata
+        let mut data = secret.data;
+        if self.cfg.engine == VaultEngine::Kv2 {
+            data = match data.remove("data") {
+                Some(Value::Object(inner)) => inner,
+                _ => anyhow::bail!("Vault secret {} has no data", url),
+            };
+        }
+        if self.cfg.engine == VaultEngine::Database {
+            debug!(
+                "Vault issued database credentials valid for {}s",
+                secret.lease_duration
+            );
+        }
+
+        let field = |key: &str| data.get(key).and_then(Value::as_str).map(str::to_string);
+        let password = field(&self.cfg.password_key).with_context(|| {
+            format!("Vault secret {} has no '{}' field", url, self.cfg.password_key)
+        })?;
+
+        // Static secrets have a lease duration too, only dynamic ones can be revoked
+        let lease = (self.cfg.engine == VaultEngine::Database && !secret.lease_id.is_empty())
+            .then(|| (secret.lease_id, Duration::from_secs(secret.lease_duration)));
+
+        Ok(Credentials {
+            username: field(&self.cfg.username_key),
+            password: Secret::new(password),
+            lease,
+        })
+    }
+
+    /// Revoke the lease of dynamic credentials, which drops the database user
+    pub async fn revoke(&self, lease_id: &str) -> Result<()> {
+        let url = format!(
+            "{}/v1/sys/leases/revoke",
+            self.cfg.address.trim_end_matches('/')
+        );
+        let resp = self
+            .request(Method::PUT, &url)?
+            .json(&json!({ "lease_id": lease_id }))
+            .send()
+            .await?;
+        let status = resp.status();
+        if !status.is_success() {
+            anyhow::bail!("Vault lease revocation failed with status {}", status);
+        }
+        Ok(())
+    }
+}
@@ -1,37 +1,138 @@
 #![allow(dead_code)]
 
 use crate::core::context::Context;
-use crate::services::status::DatabaseStatus;
-use crate::utils::redis_client;
+use crate::services::config::DatabaseConfig;
+use crate::services::status::{DatabaseStatus, QuarantinedSchedule};
 use crate::utils::task_manager::cron::check_and_update_cron;
+use crate::utils::task_manager::tasks::remove_task;
+use crate::utils::task_manager::store::{ScheduleStore, schedule_store};
+use crate::utils::task_manager::models::MisfirePolicy;
+use serde::Serialize;
 use std::sync::Arc;
-use redis::aio::MultiplexedConnection;
+use tracing::{error, info};
+
+/// Scheduled occurrences that did not run on time because the agent was down
+#[derive(Debug, Serialize)]
+pub struct MissedRun {
+    pub name: String,
+    pub task: String,
+    #[serde(rename = "generatedId")]
+    pub generated_id: Option<String>,
+    pub policy: MisfirePolicy,
+    pub missed: usize,
+    #[serde(rename = "firstMissedAt")]
+    pub first_missed_at: i64,
+    #[serde(rename = "lastMissedAt")]
+    pub last_missed_at: i64,
+    /// Whether a single catch-up run was started for them
+    #[serde(rename = "caughtUp")]
+    pub caught_up: bool,
+}
 
 pub struct CronService {
     ctx: Arc<Context>,
-    conn: MultiplexedConnection,
+    store: Arc<dyn ScheduleStore>,
 }
 
 impl CronService {
     pub async fn new(ctx: Arc<Context>) -> Self {
-        let conn = redis_client::redis_connection().await;
-        CronService { ctx, conn }
+        let store = schedule_store().await;
+        CronService { ctx, store }
     }
 
-    pub async fn sync(&mut self, database: &DatabaseStatus) -> Result<bool, String> {
+    pub async fn sync(
+        &mut self,
+        database: &DatabaseStatus,
+        misfire: MisfirePolicy,
+    ) -> Result<bool, String> {
         let generated_id = database.generated_id.as_str();
         let dbms = database.dbms.as_str();
         let task_name = format!("periodic.backup_{}", generated_id);
         let args = vec![generated_id.to_string(), dbms.to_string()];
 
         check_and_update_cron(
-            &mut self.conn,
+            self.store.as_ref(),
             database.data.backup.cron.clone(),
             args,
+            misfire,
+            database.data.backup.timezone.clone(),
             "tasks.database.periodic_backup",
             task_name,
         ).await;
 
         Ok(true)
     }
+
+    pub async fn sync_verification(&mut self, database: &DatabaseConfig) -> Result<bool, String> {
+        let generated_id = database.generated_id.as_str();
+        let task_name = format!("periodic.verify_{}", generated_id);
+        let args = vec![generated_id.to_string(), database.db_type.as_str().to_string()];
+
+        check_and_update_cron(
+            self.store.as_ref(),
+            database.verification.as_ref().map(|v| v.cron.clone()),
+            args,
+            database.misfire,
+            database.verification.as_ref().and_then(|v| v.timezone.clone()),
+            "tasks.database.periodic_verify",
+            task_name,
+        ).await;
+
+        Ok(true)
+    }
+
+    /// Drop the scheduled tasks of a database removed from the config
+    pub async fn remove(&mut self, generated_id: &str) {
+        for prefix in ["periodic.backup", "periodic.verify"] {
+            let task_name = format!("{}_{}", prefix, generated_id);
+            match self.store.get(&task_name).await {
+                Ok(Some(_)) => match remove_task(self.store.as_ref(), &task_name).await {
+                    Ok(()) => info!("Task {} removed", task_name),
+                    Err(e) => error!("Failed to remove task {}: {:?}", task_name, e),
+                },
+                Ok(None) => {}
+                Err(e) => error!("Failed to read task {}: {:?}", task_name, e),
+            }
+        }
+    }
+
+    /// Tasks taken out of the schedule, reported with the status call
+    pub async fn quarantined(&self) -> Vec<QuarantinedSchedule> {
+        match self.store.quarantined().await {
+            Ok(errors) => errors
+                .into_iter()
+                .map(|(name, error)| QuarantinedSchedule { name, error })
+                .collect(),
+            Err(e) => {
+                error!("Failed to read quarantined tasks: {:?}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    pub async fn report_missed(&self, report: &MissedRun) {
+        let client = reqwest::Client::new();
+        let url = format!(
+            "{}/api/agent/{}/schedules/missed",
+            self.ctx.edge_key.server_url, self.ctx.edge_key.agent_id
+        );
+
+        match client.post(&url).json(report).send().await {
+            Ok(resp) => {
+                let status = resp.status();
+                if status.is_success() {
+                    info!("Missed runs of {} reported", report.name);
+                } else {
+                    let text = resp.text().await.unwrap_or_default();
+                    error!(
+                        "Missed run report failed, status: {}, body: {}",
+                        status, text
+                    );
+                }
+            }
+            Err(e) => {
+                error!("Failed to report missed runs: {}", e);
+            }
+        }
+    }
 }
@@ -0,0 +1,41 @@
+use crate::domain::{mongodb, mysql, postgres};
+use crate::services::config::{DatabaseConfig, DbType, ServerConfig};
+use crate::services::credentials;
+use anyhow::Result;
+use tracing::debug;
+
+/// Databases every server has for its own use, never backed up
+fn is_system(db_type: &DbType, name: &str) -> bool {
+    match db_type {
+        DbType::Postgresql => name == "postgres",
+        DbType::Mysql | DbType::Mariadb => matches!(
+            name,
+            "information_schema" | "performance_schema" | "mysql" | "sys"
+        ),
+        DbType::MongoDB => matches!(name, "admin" | "local" | "config"),
+    }
+}
+
+/// Entries of the databases found on `server` and selected by its patterns
+pub async fn discover(server: &ServerConfig) -> Result<Vec<DatabaseConfig>> {
+    let (admin, _lease) = credentials::resolve(server.database("")).await?;
+    let names = match server.db_type {
+        DbType::Postgresql => postgres::list_databases(&admin).await?,
+        DbType::Mysql | DbType::Mariadb => mysql::list_databases(&admin).await?,
+        DbType::MongoDB => mongodb::list_databases(&admin).await?,
+    };
+
+    let selected: Vec<DatabaseConfig> = names
+        .iter()
+        .filter(|name| !is_system(&server.db_type, name) && server.selects(name))
+        .map(|name| server.database(name))
+        .collect();
+
+    debug!(
+        "Discovered {} of {} databases on {}",
+        selected.len(),
+        names.len(),
+        server.name
+    );
+    Ok(selected)
+}
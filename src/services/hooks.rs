@@ -0,0 +1,83 @@
+use crate::services::config::{DatabaseConfig, limit};
+use crate::utils::process::ProcessControl;
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::process::Command;
+use tracing::{error, info};
+
+/// Point of an operation a hook runs at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookPoint {
+    PreBackup,
+    PostBackup,
+    PreRestore,
+    PostRestore,
+}
+
+impl HookPoint {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookPoint::PreBackup => "pre_backup",
+            HookPoint::PostBackup => "post_backup",
+            HookPoint::PreRestore => "pre_restore",
+            HookPoint::PostRestore => "post_restore",
+        }
+    }
+}
+
+/// Run the database's hook for `point`, if it has one. `file` is the dump
+/// being produced or restored, `status` the outcome for post hooks.
+pub async fn run(
+    cfg: &DatabaseConfig,
+    point: HookPoint,
+    file: Option<&Path>,
+    status: Option<&str>,
+) -> Result<()> {
+    let hooks = cfg.hooks();
+    let command = match point {
+        HookPoint::PreBackup => hooks.pre_backup,
+        HookPoint::PostBackup => hooks.post_backup,
+        HookPoint::PreRestore => hooks.pre_restore,
+        HookPoint::PostRestore => hooks.post_restore,
+    };
+    let Some((program, args)) = command.as_deref().and_then(|c| c.split_first()) else {
+        return Ok(());
+    };
+
+    info!("Running {} hook for {}: {}", point.as_str(), cfg.generated_id, program);
+    let mut cmd = Command::new(program);
+    cmd.args(args)
+        .env("GENERATED_ID", &cfg.generated_id)
+        .env("DATABASE", &cfg.database)
+        .env("DATABASE_TYPE", cfg.db_type.as_str())
+        .env("HOOK", point.as_str());
+    if let Some(file) = file {
+        cmd.env("BACKUP_FILE", file);
+    }
+    if let Some(status) = status {
+        cmd.env("STATUS", status);
+    }
+
+    let control = ProcessControl::current(limit(hooks.timeout));
+    let output = tokio::task::spawn_blocking(move || control.output(&mut cmd))
+        .await?
+        .with_context(|| format!("Failed to run {} hook {}", point.as_str(), program))?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} hook {} failed with status {}: {}",
+            point.as_str(),
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Run a post hook, whose failure is logged without affecting the operation
+pub async fn run_after(cfg: &DatabaseConfig, point: HookPoint, file: Option<&Path>, status: &str) {
+    if let Err(e) = run(cfg, point, file, Some(status)).await {
+        error!("{:#}", e);
+    }
+}
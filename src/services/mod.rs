@@ -2,4 +2,13 @@ pub mod config;
 pub mod status;
 pub mod cron;
 pub mod backup;
-pub mod restore;
\ No newline at end of file
+pub mod restore;
+pub mod verify;
+pub mod outbox;
+pub mod upload;
+pub mod storage;
+pub mod progress;
+pub mod credentials;
+pub mod discovery;
+pub mod options;
+pub mod hooks;
@@ -0,0 +1,206 @@
+use crate::services::config::DbType;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Engine-specific settings of a database, the `options` block of its entry,
+/// read according to the entry's `type`
+#[derive(Debug, Clone, PartialEq)]
+pub enum DatabaseOptions {
+    Postgres(PostgresOptions),
+    Mysql(MysqlOptions),
+    Mongo(MongoOptions),
+}
+
+impl DatabaseOptions {
+    /// Options of a `db_type` database, rejecting keys that engine does not know
+    pub fn parse(db_type: &DbType, raw: Option<&Value>) -> Result<Self, String> {
+        let raw = raw.cloned().unwrap_or_else(|| Value::Object(Default::default()));
+        let parsed = match db_type {
+            DbType::Postgresql => serde_json::from_value(raw).map(DatabaseOptions::Postgres),
+            DbType::Mysql | DbType::Mariadb => {
+                serde_json::from_value(raw).map(DatabaseOptions::Mysql)
+            }
+            DbType::MongoDB => serde_json::from_value(raw).map(DatabaseOptions::Mongo),
+        };
+        parsed.map_err(|e| format!("invalid {} options: {}", db_type.as_str(), e))
+    }
+
+    /// The same options dumping every schema, table and collection
+    pub fn without_filters(&self) -> Self {
+        match self.clone() {
+            DatabaseOptions::Postgres(o) => DatabaseOptions::Postgres(PostgresOptions {
+                schemas: Vec::new(),
+                exclude_schemas: Vec::new(),
+                tables: Vec::new(),
+                exclude_tables: Vec::new(),
+                ..o
+            }),
+            DatabaseOptions::Mysql(o) => DatabaseOptions::Mysql(MysqlOptions {
+                tables: Vec::new(),
+                exclude_tables: Vec::new(),
+                ..o
+            }),
+            DatabaseOptions::Mongo(o) => DatabaseOptions::Mongo(MongoOptions {
+                exclude_collections: Vec::new(),
+                ..o
+            }),
+        }
+    }
+
+    /// Whether the dumps leave out schemas, tables or collections
+    pub fn is_filtered(&self) -> bool {
+        *self != self.without_filters()
+    }
+
+    pub fn hooks(&self) -> &Hooks {
+        match self {
+            DatabaseOptions::Postgres(o) => &o.hooks,
+            DatabaseOptions::Mysql(o) => &o.hooks,
+            DatabaseOptions::Mongo(o) => &o.hooks,
+        }
+    }
+}
+
+/// Arguments appended to the dump and restore commands
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExtraArgs {
+    #[serde(default)]
+    pub backup: Vec<String>,
+    #[serde(default)]
+    pub restore: Vec<String>,
+}
+
+/// Commands (program then arguments) run around operations, with
+/// `GENERATED_ID`, `DATABASE` and `BACKUP_FILE` in their environment.
+/// A failing `pre_*` hook fails the operation, a failing `post_*` hook is logged.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Hooks {
+    #[serde(default)]
+    pub pre_backup: Option<Vec<String>>,
+    #[serde(default)]
+    pub post_backup: Option<Vec<String>>,
+    #[serde(default)]
+    pub pre_restore: Option<Vec<String>>,
+    #[serde(default)]
+    pub post_restore: Option<Vec<String>>,
+    /// Seconds before a hook is killed, 0 for no limit
+    #[serde(default = "default_hook_timeout")]
+    pub timeout: u64,
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        Hooks {
+            pre_backup: None,
+            post_backup: None,
+            pre_restore: None,
+            post_restore: None,
+            timeout: default_hook_timeout(),
+        }
+    }
+}
+
+fn default_hook_timeout() -> u64 {
+    300
+}
+
+/// Seconds the PostgreSQL and MySQL clients wait for a connection by default
+pub const DEFAULT_CONNECT_TIMEOUT: u64 = 10;
+
+/// Encryption of the connections to the database server, modes named after `sslmode`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsOptions {
+    #[serde(default)]
+    pub mode: TlsMode,
+    /// CA bundle the server certificate is verified against
+    pub ca_file: Option<String>,
+    /// Client certificate, for MongoDB a PEM holding the key as well
+    pub cert_file: Option<String>,
+    pub key_file: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TlsMode {
+    Disable,
+    Prefer,
+    /// Encrypted, without verifying the server certificate
+    #[default]
+    Require,
+    /// Server certificate signed by the CA, whatever its host name
+    VerifyCa,
+    /// Server certificate signed by the CA and matching the host
+    VerifyFull,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PostgresFormat {
+    /// Directory format above 1 GB, custom format below
+    #[default]
+    Auto,
+    Custom,
+    Directory,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PostgresOptions {
+    #[serde(default)]
+    pub format: PostgresFormat,
+    /// Parallel jobs of directory format dumps and restores
+    pub jobs: Option<u32>,
+    /// Compression level of custom format dumps
+    pub compress: Option<u32>,
+    /// Only these schemas (`pg_dump -n`)
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(default)]
+    pub exclude_schemas: Vec<String>,
+    /// Only these tables (`pg_dump -t`)
+    #[serde(default)]
+    pub tables: Vec<String>,
+    #[serde(default)]
+    pub exclude_tables: Vec<String>,
+    /// Seconds to wait for a connection, `DEFAULT_CONNECT_TIMEOUT` when unset
+    pub connect_timeout: Option<u64>,
+    pub tls: Option<TlsOptions>,
+    #[serde(default)]
+    pub extra_args: ExtraArgs,
+    #[serde(default)]
+    pub hooks: Hooks,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MysqlOptions {
+    /// Only these tables
+    #[serde(default)]
+    pub tables: Vec<String>,
+    #[serde(default)]
+    pub exclude_tables: Vec<String>,
+    /// Seconds to wait for a connection, `DEFAULT_CONNECT_TIMEOUT` when unset
+    pub connect_timeout: Option<u64>,
+    pub tls: Option<TlsOptions>,
+    #[serde(default)]
+    pub extra_args: ExtraArgs,
+    #[serde(default)]
+    pub hooks: Hooks,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MongoOptions {
+    #[serde(default)]
+    pub exclude_collections: Vec<String>,
+    /// Seconds to wait for a connection
+    pub connect_timeout: Option<u64>,
+    pub tls: Option<TlsOptions>,
+    #[serde(default)]
+    pub extra_args: ExtraArgs,
+    #[serde(default)]
+    pub hooks: Hooks,
+}
@@ -0,0 +1,446 @@
+use crate::core::context::Context;
+use crate::services::backup::{BackupPayload, BackupService};
+use crate::services::restore::{RestoreResult, RestoreService};
+use crate::settings::CONFIG;
+use crate::utils::metrics;
+use anyhow::{Context as _, Result};
+use chrono::Utc;
+use openssl::rand::rand_bytes;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::fs;
+use tracing::{error, info, warn};
+
+const ENTRY_FILE: &str = "entry.json";
+const ARTIFACT_FILE: &str = "artifact.enc";
+/// Locked while the entry is delivered or evicted, by any agent process
+const LOCK_FILE: &str = "lock";
+const BASE_BACKOFF_SECS: u64 = 5;
+const MAX_BACKOFF_SECS: u64 = 60 * 60;
+/// Staging directories older than this were left behind by a crash mid-enqueue
+const STALE_STAGING_SECS: u64 = 60 * 60;
+
+/// Result waiting for the server to acknowledge it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum OutboxPayload {
+    Backup(Box<BackupPayload>),
+    Restore(RestoreResult),
+}
+
+impl OutboxPayload {
+    pub fn generated_id(&self) -> &str {
+        match self {
+            OutboxPayload::Backup(p) => &p.generated_id,
+            OutboxPayload::Restore(r) => &r.generated_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub id: String,
+    pub payload: OutboxPayload,
+    pub created_at: i64,
+    pub attempts: u32,
+    pub next_attempt_at: i64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum DeliveryError {
+    /// The server answered and refused the payload, retrying will not help.
+    /// Authentication errors, 404, 408 and 429 are transient: they follow a
+    /// rotated key, a server being deployed or rate limiting.
+    #[error("server rejected delivery with status {0}: {1}")]
+    Rejected(StatusCode, String),
+    #[error("{0}")]
+    Transient(String),
+}
+
+impl DeliveryError {
+    pub fn from_response(status: StatusCode, body: String) -> Self {
+        let retryable = matches!(
+            status,
+            StatusCode::UNAUTHORIZED
+                | StatusCode::FORBIDDEN
+                | StatusCode::NOT_FOUND
+                | StatusCode::REQUEST_TIMEOUT
+                | StatusCode::TOO_MANY_REQUESTS
+        );
+        if status.is_client_error() && !retryable {
+            DeliveryError::Rejected(status, body)
+        } else {
+            DeliveryError::Transient(format!("status: {}, body: {}", status, body))
+        }
+    }
+}
+
+impl From<reqwest::Error> for DeliveryError {
+    fn from(e: reqwest::Error) -> Self {
+        DeliveryError::Transient(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for DeliveryError {
+    fn from(e: std::io::Error) -> Self {
+        DeliveryError::Transient(e.to_string())
+    }
+}
+
+/// Persistent queue of results under `DATA_PATH/outbox`, one directory per entry
+pub struct Outbox;
+
+impl Outbox {
+    fn dir() -> PathBuf {
+        Path::new(&CONFIG.data_path).join("outbox")
+    }
+
+    /// Entries the server rejected, kept for inspection instead of being deleted
+    fn rejected_dir() -> PathBuf {
+        Path::new(&CONFIG.data_path).join("outbox-rejected")
+    }
+
+    fn entry_dir(id: &str) -> PathBuf {
+        Self::dir().join(id)
+    }
+
+    pub fn artifact_path(id: &str) -> PathBuf {
+        Self::entry_dir(id).join(ARTIFACT_FILE)
+    }
+
+    /// Ids sort by creation time so that listing the directory yields FIFO order
+    fn new_id() -> String {
+        let mut suffix = [0u8; 4];
+        rand_bytes(&mut suffix).ok();
+        format!(
+            "{:013}-{}",
+            Utc::now().timestamp_millis(),
+            hex::encode(suffix)
+        )
+    }
+
+    async fn create_staging() -> Result<(String, PathBuf)> {
+        let id = Self::new_id();
+        let staging = Self::dir().join(format!("{}.tmp", id));
+        fs::create_dir_all(&staging)
+            .await
+            .with_context(|| format!("Failed to create outbox entry {:?}", staging))?;
+        Ok((id, staging))
+    }
+
+    /// Path of the artifact of a new entry, to be written before `enqueue`
+    pub async fn stage() -> Result<PathBuf> {
+        let (_, staging) = Self::create_staging().await?;
+        Ok(staging.join(ARTIFACT_FILE))
+    }
+
+    /// Drop an entry staged with `stage` that will not be queued
+    pub async fn unstage(artifact: &Path) {
+        if let Some(staging) = artifact.parent()
+            && let Err(e) = fs::remove_dir_all(staging).await
+        {
+            warn!("[Outbox] Failed to remove {:?}: {}", staging, e);
+        }
+    }
+
+    /// Persist a payload with its encrypted artifact, written at a path from
+    /// `stage`, returns the entry id
+    pub async fn enqueue(payload: OutboxPayload, artifact: Option<&Path>) -> Result<String> {
+        let (id, staging) = match artifact.and_then(Path::parent) {
+            Some(staging) => {
+                let name = staging.file_name().unwrap_or_default().to_string_lossy();
+                let Some(id) = name.strip_suffix(".tmp") else {
+                    anyhow::bail!("Artifact {:?} was not staged in the outbox", artifact);
+                };
+                (id.to_string(), staging.to_path_buf())
+            }
+            None => Self::create_staging().await?,
+        };
+
+        let now = Utc::now().timestamp();
+        let entry = OutboxEntry {
+            id: id.clone(),
+            payload,
+            created_at: now,
+            attempts: 0,
+            next_attempt_at: now,
+            last_error: None,
+        };
+        fs::write(staging.join(ENTRY_FILE), serde_json::to_vec(&entry)?).await?;
+        fs::rename(&staging, Self::entry_dir(&id)).await?;
+
+        info!(
+            "[Outbox] Queued entry {} for DB {}",
+            id,
+            entry.payload.generated_id()
+        );
+
+        Self::enforce_cap(&id).await;
+        Ok(id)
+    }
+
+    async fn load(id: &str) -> Result<OutboxEntry> {
+        let raw = fs::read(Self::entry_dir(id).join(ENTRY_FILE)).await?;
+        Ok(serde_json::from_slice(&raw)?)
+    }
+
+    async fn save(entry: &OutboxEntry) -> Result<()> {
+        let dir = Self::entry_dir(&entry.id);
+        let tmp = dir.join(format!("{}.tmp", ENTRY_FILE));
+        fs::write(&tmp, serde_json::to_vec(entry)?).await?;
+        fs::rename(&tmp, dir.join(ENTRY_FILE)).await?;
+        Ok(())
+    }
+
+    async fn remove(id: &str) {
+        if let Err(e) = fs::remove_dir_all(Self::entry_dir(id)).await {
+            warn!("[Outbox] Failed to remove entry {}: {}", id, e);
+        }
+    }
+
+    /// Move a rejected entry out of the queue, into `DATA_PATH/outbox-rejected`
+    async fn reject(id: &str) {
+        let dir = Self::rejected_dir();
+        let moved = match fs::create_dir_all(&dir).await {
+            Ok(()) => fs::rename(Self::entry_dir(id), dir.join(id)).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = moved {
+            warn!("[Outbox] Failed to move rejected entry {}: {}", id, e);
+            Self::remove(id).await;
+        }
+    }
+
+    /// Remove staging directories of enqueues that never completed
+    async fn clean_staging() {
+        let Ok(mut dir) = fs::read_dir(Self::dir()).await else {
+            return;
+        };
+        while let Ok(Some(entry)) = dir.next_entry().await {
+            if !entry.file_name().to_string_lossy().ends_with(".tmp") {
+                continue;
+            }
+            let stale = entry
+                .metadata()
+                .await
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|m| m.elapsed().ok())
+                .is_some_and(|age| age.as_secs() > STALE_STAGING_SECS);
+            if stale {
+                warn!(
+                    "[Outbox] Removing stale staging directory {:?}",
+                    entry.path()
+                );
+                if let Err(e) = fs::remove_dir_all(entry.path()).await {
+                    warn!("[Outbox] Failed to remove {:?}: {}", entry.path(), e);
+                }
+            }
+        }
+    }
+
+    /// Committed entry ids, oldest first
+    async fn list() -> Vec<String> {
+        Self::list_in(&Self::dir()).await
+    }
+
+    async fn list_in(path: &Path) -> Vec<String> {
+        let mut ids = Vec::new();
+        let Ok(mut dir) = fs::read_dir(path).await else {
+            return ids;
+        };
+        while let Ok(Some(entry)) = dir.next_entry().await {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.ends_with(".tmp") && entry.path().join(ENTRY_FILE).exists() {
+                ids.push(name);
+            }
+        }
+        ids.sort();
+        ids
+    }
+
+    /// Newest backup of `generated_id` still queued with its artifact
+    pub async fn latest_backup(generated_id: &str) -> Option<(PathBuf, BackupPayload)> {
+        let mut latest: Option<(PathBuf, BackupPayload)> = None;
+        for id in Self::list().await {
+            let Ok(entry) = Self::load(&id).await else {
+                continue;
+            };
+            let OutboxPayload::Backup(payload) = entry.payload else {
+                continue;
+            };
+            let artifact = Self::artifact_path(&id);
+            if payload.generated_id == generated_id
+                && payload.artifact.is_some()
+                && artifact.exists()
+                && latest
+                    .as_ref()
+                    .is_none_or(|(_, newest)| payload.timestamp > newest.timestamp)
+            {
+                latest = Some((artifact, *payload));
+            }
+        }
+        latest
+    }
+
+    /// Exclusive lock of an entry, released when the file is dropped. None
+    /// when another delivery holds it or the entry is gone.
+    fn try_lock(id: &str) -> Option<File> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(Self::entry_dir(id).join(LOCK_FILE))
+            .ok()?;
+        file.try_lock().ok()?;
+        Some(file)
+    }
+
+    async fn size_of(entry_dir: &Path) -> u64 {
+        let mut size = 0;
+        if let Ok(mut dir) = fs::read_dir(entry_dir).await {
+            while let Ok(Some(entry)) = dir.next_entry().await {
+                if let Ok(meta) = entry.metadata().await {
+                    size += meta.len();
+                }
+            }
+        }
+        size
+    }
+
+    /// Evict the oldest entries until the outbox and its rejected entries fit
+    /// in `OUTBOX_MAX_SIZE_MB`, rejected ones first, never touching `keep`
+    /// (the entry that was just queued)
+    async fn enforce_cap(keep: &str) {
+        let rejected_dir = Self::rejected_dir();
+        let mut sizes = Vec::new();
+        for id in Self::list_in(&rejected_dir).await {
+            let dir = rejected_dir.join(&id);
+            let size = Self::size_of(&dir).await;
+            sizes.push((id, dir, size, true));
+        }
+        for id in Self::list().await {
+            let dir = Self::entry_dir(&id);
+            let size = Self::size_of(&dir).await;
+            sizes.push((id, dir, size, false));
+        }
+
+        let mut total: u64 = sizes.iter().map(|(_, _, s, _)| s).sum();
+        for (id, dir, size, rejected) in sizes {
+            if total <= CONFIG.outbox_max_size {
+                break;
+            }
+            // Entries being delivered are skipped, the lock keeps them from starting meanwhile
+            let _lock = if rejected {
+                None
+            } else {
+                match Self::try_lock(&id) {
+                    Some(lock) if id != keep => Some(lock),
+                    _ => continue,
+                }
+            };
+            warn!(
+                "[Outbox] Size cap of {} bytes exceeded, evicting {}entry {} ({} bytes)",
+                CONFIG.outbox_max_size,
+                if rejected { "rejected " } else { "" },
+                id,
+                size
+            );
+            if let Err(e) = fs::remove_dir_all(&dir).await {
+                warn!("[Outbox] Failed to remove {:?}: {}", dir, e);
+            }
+            total -= size;
+        }
+    }
+
+    /// Exponential backoff with jitter in [50%, 100%] of the nominal delay
+    fn backoff(attempts: u32) -> i64 {
+        let exp = BASE_BACKOFF_SECS.saturating_mul(1u64 << attempts.saturating_sub(1).min(16));
+        let nominal = exp.min(MAX_BACKOFF_SECS);
+        let mut byte = [0u8; 1];
+        rand_bytes(&mut byte).ok();
+        let jitter = 0.5 + (byte[0] as f64 / 255.0) * 0.5;
+        (nominal as f64 * jitter).ceil() as i64
+    }
+
+    /// Try to deliver one entry, returns true once the server acknowledged it
+    pub async fn deliver(ctx: &Arc<Context>, id: &str) -> bool {
+        let Some(_lock) = Self::try_lock(id) else {
+            return false;
+        };
+        Self::try_deliver(ctx, id).await
+    }
+
+    async fn try_deliver(ctx: &Arc<Context>, id: &str) -> bool {
+        let mut entry = match Self::load(id).await {
+            Ok(entry) => entry,
+            Err(e) => {
+                error!("[Outbox] Dropping unreadable entry {}: {}", id, e);
+                Self::remove(id).await;
+                return false;
+            }
+        };
+
+        let artifact = Self::artifact_path(id);
+        let result = match &mut entry.payload {
+            OutboxPayload::Backup(payload) => {
+                let artifact = artifact.exists().then_some(artifact.as_path());
+                BackupService::deliver(ctx, payload, artifact).await
+            }
+            OutboxPayload::Restore(result) => RestoreService::deliver(ctx, result).await,
+        };
+
+        match result {
+            Ok(()) => {
+                info!("[Outbox] Entry {} acknowledged by server", id);
+                Self::remove(id).await;
+                true
+            }
+            Err(DeliveryError::Rejected(status, body)) => {
+                error!(
+                    "[Outbox] Entry {} rejected by server, moving it to {:?}. status: {}, body: {}",
+                    id,
+                    Self::rejected_dir(),
+                    status,
+                    body
+                );
+                Self::reject(id).await;
+                false
+            }
+            Err(DeliveryError::Transient(e)) => {
+                entry.attempts += 1;
+                let delay = Self::backoff(entry.attempts);
+                entry.next_attempt_at = Utc::now().timestamp() + delay;
+                entry.last_error = Some(e.clone());
+                metrics::record_upload_retry("delivery");
+                warn!(
+                    "[Outbox] Delivery of entry {} failed (attempt {}), retrying in {}s: {}",
+                    id, entry.attempts, delay, e
+                );
+                if let Err(e) = Self::save(&entry).await {
+                    error!("[Outbox] Failed to update entry {}: {}", id, e);
+                }
+                false
+            }
+        }
+    }
+
+    /// Deliver every entry whose retry time has come
+    pub async fn flush(ctx: &Arc<Context>) {
+        Self::clean_staging().await;
+        let now = Utc::now().timestamp();
+        for id in Self::list().await {
+            match Self::load(&id).await {
+                Ok(entry) if entry.next_attempt_at > now => continue,
+                _ => {
+                    Self::deliver(ctx, &id).await;
+                }
+            }
+        }
+    }
+}
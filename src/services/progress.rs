@@ -0,0 +1,92 @@
+use crate::core::context::Context;
+use crate::settings::CONFIG;
+use crate::utils::progress::{JOB_PROGRESS, Progress, ProgressSnapshot};
+use chrono::Utc;
+use reqwest::Client;
+use serde::Serialize;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Body of the job progress request
+#[derive(Serialize)]
+struct ProgressEvent<'a> {
+    #[serde(rename = "generatedId")]
+    generated_id: &'a str,
+    kind: &'a str,
+    #[serde(flatten)]
+    snapshot: &'a ProgressSnapshot,
+    timestamp: i64,
+}
+
+/// Periodically posts the progress of one job, until dropped.
+/// Events are best effort: a failed post is not retried, the next one supersedes it.
+pub struct ProgressReporter {
+    handle: JoinHandle<()>,
+}
+
+impl ProgressReporter {
+    /// Run `work` as job `job_id` with a fresh progress, reported while it runs
+    pub async fn track<F: Future>(
+        ctx: Arc<Context>,
+        job_id: &str,
+        generated_id: &str,
+        kind: &'static str,
+        work: F,
+    ) -> F::Output {
+        let progress = Progress::new();
+        let _reporter = Self::start(ctx, job_id, generated_id, kind, progress.clone());
+        JOB_PROGRESS.scope(progress, work).await
+    }
+
+    fn start(
+        ctx: Arc<Context>,
+        job_id: &str,
+        generated_id: &str,
+        kind: &'static str,
+        progress: Progress,
+    ) -> Self {
+        let url = format!(
+            "{}/api/agent/{}/jobs/{}/progress",
+            ctx.edge_key.server_url, ctx.edge_key.agent_id, job_id
+        );
+        let generated_id = generated_id.to_string();
+
+        let handle = tokio::spawn(async move {
+            let client = Client::new();
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(CONFIG.progress_interval));
+            // The first tick completes immediately, nothing to report yet
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let Some(snapshot) = progress.snapshot().await else {
+                    return;
+                };
+                let event = ProgressEvent {
+                    generated_id: &generated_id,
+                    kind,
+                    snapshot: &snapshot,
+                    timestamp: Utc::now().timestamp(),
+                };
+                match client.post(&url).json(&event).send().await {
+                    Ok(resp) if resp.status().is_success() => {
+                        debug!("Progress sent | {} {:?}", url, snapshot)
+                    }
+                    Ok(resp) => warn!("Progress request failed with status: {}", resp.status()),
+                    Err(e) => warn!("Progress request failed: {}", e),
+                }
+            }
+        });
+
+        ProgressReporter { handle }
+    }
+}
+
+impl Drop for ProgressReporter {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
@@ -1,21 +1,32 @@
 #![allow(dead_code)]
 
 use crate::core::context::Context;
-use crate::domain::factory::DatabaseFactory;
+use crate::utils::process::was_cancelled;
+use crate::core::executor::{Executor, Job, Priority, Submitted};
+use crate::domain::factory::{DatabaseFactory, RestoreScope};
 use crate::services::config::{DatabaseConfig, DatabasesConfig};
+use crate::services::credentials;
+use crate::services::hooks::{self, HookPoint};
+use crate::services::outbox::{DeliveryError, Outbox, OutboxPayload};
+use crate::services::progress::ProgressReporter;
+use crate::utils::progress::{Phase, Progress};
 use crate::services::status::DatabaseStatus;
+use crate::utils::metrics;
 use anyhow::Result;
 use tracing::{error, info};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Instant;
 use tempfile::TempDir;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RestoreResult {
     #[serde(rename = "generatedId")]
     pub generated_id: String,
     pub status: String,
+    #[serde(rename = "jobId", default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
 }
 
 pub struct RestoreService {
@@ -36,48 +47,125 @@ impl RestoreService {
             let db_cfg = cfg.clone();
             let ctx_clone = self.ctx.clone();
             let file_to_restore = db.data.restore.file.clone();
+            let scope = RestoreScope::of(db.data.restore.filtered);
 
-            tokio::spawn(async move {
-                match TempDir::new() {
-                    Ok(temp_dir) => {
-                        let tmp_path = temp_dir.path().to_path_buf();
-                        info!("Created temp directory {}", tmp_path.display());
+            // Restores are always requested by an operator
+            let job = Job::new(
+                &cfg.host,
+                Priority::Manual,
+                "restore",
+                &db.generated_id,
+                |job_id| async move {
+                    let generated_id = db_cfg.generated_id.clone();
+                    let work = async {
+                        match TempDir::new() {
+                            Ok(temp_dir) => {
+                                let tmp_path = temp_dir.path().to_path_buf();
+                                info!("Created temp directory {}", tmp_path.display());
 
-                        match RestoreService::run(db_cfg, &tmp_path, &file_to_restore).await {
-                            Ok(result) => {
-                                let service = RestoreService { ctx: ctx_clone };
-                                service.send_result(result).await;
+                                let started = Instant::now();
+                                match RestoreService::run(db_cfg, &tmp_path, &file_to_restore, scope)
+                                    .await
+                                {
+                                    Ok(mut result) => {
+                                        metrics::record_restore(
+                                            &generated_id,
+                                            &result.status,
+                                            started.elapsed(),
+                                        );
+                                        result.job_id = Some(job_id.clone());
+                                        let service = RestoreService {
+                                            ctx: ctx_clone.clone(),
+                                        };
+                                        service.send_result(result).await;
+                                    }
+                                    Err(e) => {
+                                        metrics::record_restore(
+                                            &generated_id,
+                                            "failed",
+                                            started.elapsed(),
+                                        );
+                                        error!("Restoration error {}", e)
+                                    }
+                                }
+                                // TempDir is automatically deleted when dropped here
                             }
-                            Err(e) => error!("Restoration error {}", e),
+                            Err(e) => error!("Failed to create temp dir: {}", e),
                         }
-                        // TempDir is automatically deleted when dropped here
-                    }
-                    Err(e) => error!("Failed to create temp dir: {}", e),
-                }
+                    };
+                    ProgressReporter::track(ctx_clone.clone(), &job_id, &generated_id, "restore", work)
+                        .await;
+                },
+            );
+            let service = RestoreService {
+                ctx: self.ctx.clone(),
+            };
+            let generated_id = db.generated_id.clone();
+            let job = job.on_cancel(|job_id| async move {
+                service
+                    .send_result(RestoreResult {
+                        generated_id,
+                        status: "cancelled".into(),
+                        job_id: Some(job_id),
+                    })
+                    .await;
             });
+
+            let job_id = job.id.clone();
+            if let Submitted::Queued = Executor::submit(job) {
+                self.send_result(RestoreResult {
+                    generated_id: db.generated_id.clone(),
+                    status: "queued".into(),
+                    job_id: Some(job_id),
+                })
+                .await;
+            }
         }
     }
 
+    /// Restore the dump at `file_url`. Only URLs are fetched, never local
+    /// paths, as the URL may come from the server.
     pub async fn run(
         cfg: DatabaseConfig,
         tmp_path: &Path,
         file_url: &str,
+        scope: RestoreScope,
     ) -> Result<RestoreResult> {
-        let generated_id = cfg.generated_id.clone();
-
         info!("File url: {}", file_url);
 
+        let progress = Progress::current();
+        progress.phase(Phase::Downloading);
+
         let client = reqwest::Client::new();
-        let response = client.get(file_url).send().await?;
+        let mut response = client.get(file_url).send().await?;
         if !response.status().is_success() {
             error!("Backup download failed with status {}", response.status());
             return Ok(RestoreResult {
-                generated_id,
+                generated_id: cfg.generated_id,
                 status: "failed".into(),
+                job_id: None,
             });
         }
 
-        let bytes = response.bytes().await?;
+        let total = response.content_length();
+        let mut bytes = Vec::with_capacity(total.unwrap_or(0) as usize);
+        while let Some(chunk) = response.chunk().await? {
+            bytes.extend_from_slice(&chunk);
+            progress.bytes(bytes.len() as u64, total);
+        }
+
+        Self::restore(cfg, tmp_path, bytes, scope).await
+    }
+
+    /// Restore a dump already in memory, such as a local file read by the CLI
+    pub async fn restore(
+        cfg: DatabaseConfig,
+        tmp_path: &Path,
+        bytes: Vec<u8>,
+        scope: RestoreScope,
+    ) -> Result<RestoreResult> {
+        let generated_id = cfg.generated_id.clone();
+        let progress = Progress::current();
 
         let ext = if bytes.starts_with(b"PGDMP") {
             // Postgres custom format
@@ -99,6 +187,17 @@ impl RestoreService {
         tokio::fs::write(&backup_file_path, &bytes).await?;
         info!("Backup downloaded to {}", backup_file_path.display());
 
+        let (cfg, _lease) = match credentials::resolve(cfg).await {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                error!("{:#}", e);
+                return Ok(RestoreResult {
+                    generated_id,
+                    status: "failed".into(),
+                    job_id: None,
+                });
+            }
+        };
         let db_instance = DatabaseFactory::create_for_restore(cfg.clone(), &backup_file_path).await;
         let reachable = db_instance.ping().await.unwrap_or(false);
         info!("Reachable: {}", reachable);
@@ -106,19 +205,41 @@ impl RestoreService {
             return Ok(RestoreResult {
                 generated_id,
                 status: "failed".into(),
+                job_id: None,
             });
         }
 
-        match db_instance.restore(&backup_file_path).await {
+        if let Err(e) = hooks::run(&cfg, HookPoint::PreRestore, Some(&backup_file_path), None).await
+        {
+            error!("{:#}", e);
+            return Ok(RestoreResult {
+                generated_id,
+                status: if was_cancelled(&e) { "cancelled" } else { "failed" }.into(),
+                job_id: None,
+            });
+        }
+
+        progress.phase(Phase::Restoring);
+        let result = db_instance.restore(&backup_file_path, scope).await;
+        let status = match &result {
+            Ok(_) => "success",
+            Err(e) if was_cancelled(e) => "cancelled",
+            Err(_) => "failed",
+        };
+        hooks::run_after(&cfg, HookPoint::PostRestore, Some(&backup_file_path), status).await;
+
+        match result {
             Ok(_) => Ok(RestoreResult {
                 generated_id,
                 status: "success".into(),
+                job_id: None,
             }),
             Err(e) => {
                 log::error!("Restore failed: {:?}", e);
                 Ok(RestoreResult {
                     generated_id,
-                    status: "failed".into(),
+                    status: if was_cancelled(&e) { "cancelled" } else { "failed" }.into(),
+                    job_id: None,
                 })
             }
         }
@@ -130,33 +251,33 @@ impl RestoreService {
             result.generated_id, result.status,
         );
 
+        match Outbox::enqueue(OutboxPayload::Restore(result), None).await {
+            Ok(id) => {
+                Outbox::deliver(&self.ctx, &id).await;
+            }
+            Err(e) => error!("Failed to queue restoration result: {}", e),
+        }
+    }
+
+    pub async fn deliver(ctx: &Context, result: &RestoreResult) -> Result<(), DeliveryError> {
         let client = reqwest::Client::new();
         let url = format!(
             "{}/api/agent/{}/restore",
-            self.ctx.edge_key.server_url, self.ctx.edge_key.agent_id
+            ctx.edge_key.server_url, ctx.edge_key.agent_id
         );
 
-        let body = RestoreResult {
-            generated_id: result.generated_id,
-            status: result.status,
-        };
-
-        match client.post(&url).json(&body).send().await {
-            Ok(resp) => {
-                let status = resp.status();
-                if status.is_success() {
-                    info!("Restoration result sent successfully");
-                } else {
-                    let text = resp.text().await.unwrap_or_default(); // consumes resp
-                    error!(
-                        "Restoration result failed, status: {}, body: {}",
-                        status, text
-                    );
-                }
-            }
-            Err(e) => {
-                error!("Failed to send restoration result: {}", e);
-            }
+        let resp = client.post(&url).json(result).send().await?;
+        let status = resp.status();
+        if status.is_success() {
+            info!("Restoration result sent successfully");
+            Ok(())
+        } else {
+            let text = resp.text().await.unwrap_or_default(); // consumes resp
+            error!(
+                "Restoration result failed, status: {}, body: {}",
+                status, text
+            );
+            Err(DeliveryError::from_response(status, text))
         }
     }
 }
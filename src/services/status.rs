@@ -3,10 +3,13 @@
 use crate::core::context::Context;
 use crate::services::config::DatabaseConfig;
 use crate::settings::CONFIG;
+use crate::utils::health::SERVER_REACHED;
+use crate::utils::metrics;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::sync::Arc;
+use std::time::Instant;
 use tracing::{error, info};
 
 /// Payload for sending database info in the request
@@ -18,11 +21,20 @@ struct DatabasePayload<'a> {
     generated_id: &'a str,
 }
 
+/// Scheduled task quarantined because it could not be evaluated or decoded
+#[derive(Debug, Serialize)]
+pub struct QuarantinedSchedule {
+    pub name: String,
+    pub error: String,
+}
+
 /// Body for the status API request
 #[derive(Serialize)]
 struct StatusRequestBody<'a> {
     version: &'a str,
     databases: Vec<DatabasePayload<'a>>,
+    #[serde(rename = "scheduleErrors")]
+    schedule_errors: &'a [QuarantinedSchedule],
 }
 
 /// Typed structs for the response
@@ -30,6 +42,9 @@ struct StatusRequestBody<'a> {
 pub struct PingResult {
     pub agent: AgentInfo,
     pub databases: Vec<DatabaseStatus>,
+    /// Ids of executor jobs the server wants stopped
+    #[serde(default, rename = "cancelJobs")]
+    pub cancel_jobs: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -57,12 +72,19 @@ pub struct DatabaseData {
 pub struct BackupInfo {
     pub action: bool,
     pub cron: Option<String>, // can be null
+    /// IANA timezone of the cron expression, the agent's local time when null
+    #[serde(default)]
+    pub timezone: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct RestoreInfo {
     pub action: bool,
     pub file: String,
+    /// Whether the backup to restore leaves out part of the database, as
+    /// reported with it
+    #[serde(default)]
+    pub filtered: Option<bool>,
 }
 
 /// Service for contacting the agent API
@@ -79,14 +101,29 @@ impl StatusService {
         }
     }
 
-    pub async fn ping(&self, databases: &[DatabaseConfig]) -> Result<PingResult, Box<dyn Error>> {
+    pub async fn ping(
+        &self,
+        databases: &[DatabaseConfig],
+        schedule_errors: &[QuarantinedSchedule],
+    ) -> Result<PingResult, Box<dyn Error>> {
+        let started = Instant::now();
+        let result = self.request_status(databases, schedule_errors).await;
+        metrics::record_ping(started.elapsed(), result.is_ok());
+        result
+    }
+
+    async fn request_status(
+        &self,
+        databases: &[DatabaseConfig],
+        schedule_errors: &[QuarantinedSchedule],
+    ) -> Result<PingResult, Box<dyn Error>> {
         let edge_key = &self.ctx.edge_key;
 
         let databases_payload: Vec<DatabasePayload> = databases
             .iter()
             .map(|db| DatabasePayload {
                 name: &db.name,
-                dbms: &db.db_type.as_str(),
+                dbms: db.db_type.as_str(),
                 generated_id: &db.generated_id,
             })
             .collect();
@@ -94,8 +131,9 @@ impl StatusService {
         let version_str = CONFIG.app_version.as_str();
 
         let body = StatusRequestBody {
-            version: &version_str,
+            version: version_str,
             databases: databases_payload,
+            schedule_errors,
         };
 
         let url = format!(
@@ -112,6 +150,7 @@ impl StatusService {
         }
 
         let result: PingResult = resp.json().await?;
+        SERVER_REACHED.beat();
 
         Ok(result)
     }
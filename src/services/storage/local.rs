@@ -0,0 +1,115 @@
+use crate::services::backup::{ArtifactInfo, BackupPayload};
+use crate::services::config::LocalConfig;
+use crate::services::storage::{Manifest, StoredObject, artifact_timestamp};
+use crate::utils::retention::prunable;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::{info, warn};
+
+pub struct LocalStorage<'a> {
+    cfg: &'a LocalConfig,
+}
+
+impl<'a> LocalStorage<'a> {
+    pub fn new(cfg: &'a LocalConfig) -> Self {
+        Self { cfg }
+    }
+
+    fn manifest_path(artifact: &Path) -> PathBuf {
+        let mut name = artifact.as_os_str().to_os_string();
+        name.push(".json");
+        name.into()
+    }
+
+    pub async fn put(
+        &self,
+        payload: &BackupPayload,
+        info: &ArtifactInfo,
+        artifact: &Path,
+        key: &str,
+    ) -> Result<StoredObject> {
+        let dest = Path::new(&self.cfg.path).join(key);
+        let dir = dest.parent().context("Invalid local storage key")?;
+        fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("Failed to create backup directory {:?}", dir))?;
+
+        let manifest = Manifest::new(payload, info, artifact).await?;
+
+        // Write under temporary names first so a partial copy never looks like a backup
+        let tmp = dest.with_extension("enc.tmp");
+        fs::copy(artifact, &tmp)
+            .await
+            .with_context(|| format!("Failed to copy artifact to {:?}", tmp))?;
+        let manifest_tmp = dest.with_extension("enc.json.tmp");
+        fs::write(&manifest_tmp, serde_json::to_vec_pretty(&manifest)?).await?;
+
+        fs::rename(&tmp, &dest).await?;
+        fs::rename(&manifest_tmp, Self::manifest_path(&dest)).await?;
+        info!("Artifact written to {:?}", dest);
+
+        if let Err(e) = self.prune(dir).await {
+            warn!("Retention pruning failed in {:?}: {}", dir, e);
+        }
+
+        Ok(StoredObject {
+            kind: "local".into(),
+            location: self.cfg.path.clone(),
+            key: key.to_string(),
+        })
+    }
+
+    /// Newest copy of `generated_id`, with its time
+    pub async fn latest(&self, generated_id: &str) -> Result<Option<(PathBuf, DateTime<Utc>)>> {
+        let dir = Path::new(&self.cfg.path).join(generated_id);
+        let mut entries = match fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut latest = None;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if let Some(time) = artifact_timestamp(&name)
+                && latest.as_ref().is_none_or(|(_, newest)| time > *newest)
+            {
+                latest = Some((entry.path(), time));
+            }
+        }
+        Ok(latest)
+    }
+
+    /// Manifest written next to `artifact` by `put`
+    pub async fn manifest(artifact: &Path) -> Result<Manifest> {
+        let path = Self::manifest_path(artifact);
+        let raw = fs::read(&path)
+            .await
+            .with_context(|| format!("Failed to read manifest {:?}", path))?;
+        Ok(serde_json::from_slice(&raw)?)
+    }
+
+    /// Remove the copies of one database that the retention policy no longer keeps
+    async fn prune(&self, dir: &Path) -> Result<()> {
+        if self.cfg.retention.is_empty() {
+            return Ok(());
+        }
+
+        let mut artifacts = Vec::new();
+        let mut entries = fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if let Some(time) = artifact_timestamp(&name) {
+                artifacts.push((entry.path(), time));
+            }
+        }
+
+        for path in prunable(&self.cfg.retention, artifacts) {
+            fs::remove_file(&path).await?;
+            fs::remove_file(Self::manifest_path(&path)).await.ok();
+            info!("Pruned old backup {:?}", path);
+        }
+        Ok(())
+    }
+}
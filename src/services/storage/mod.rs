@@ -0,0 +1,188 @@
+pub mod local;
+pub mod s3;
+pub mod sftp;
+
+use crate::services::backup::{ArtifactInfo, BackupPayload};
+use crate::services::config::{S3Config, SftpConfig, StorageConfig};
+use crate::services::outbox::Outbox;
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use local::LocalStorage;
+use openssl::sha::Sha256;
+use s3::S3Storage;
+use serde::{Deserialize, Serialize};
+use sftp::SftpStorage;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+use tracing::warn;
+
+/// Artifact stored outside of the Portabase server, reported with the backup result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredObject {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub location: String,
+    pub key: String,
+}
+
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Name of an artifact once copied out of its storage
+const ARTIFACT_COPY: &str = "artifact.enc";
+
+/// `<timestamp><extension>.enc`
+pub fn artifact_name(timestamp: i64, extension: &str) -> String {
+    let time = DateTime::<Utc>::from_timestamp(timestamp, 0).unwrap_or_else(Utc::now);
+    format!("{}{}.enc", time.format(TIMESTAMP_FORMAT), extension)
+}
+
+/// `<prefix><generated_id>/<timestamp><extension>.enc`
+pub fn object_key(prefix: &str, generated_id: &str, timestamp: i64, extension: &str) -> String {
+    format!(
+        "{}{}/{}",
+        prefix,
+        generated_id,
+        artifact_name(timestamp, extension)
+    )
+}
+
+/// Timestamp of an artifact named by `object_key`, `None` for anything else
+pub fn artifact_timestamp(file_name: &str) -> Option<DateTime<Utc>> {
+    if !file_name.ends_with(".enc") {
+        return None;
+    }
+    let stamp = file_name.split('.').next()?;
+    NaiveDateTime::parse_from_str(stamp, TIMESTAMP_FORMAT)
+        .ok()
+        .map(|t| t.and_utc())
+}
+
+/// Written next to each artifact as `<artifact>.json`, enough to decrypt and restore it.
+/// `filtered` tells whether the dump leaves out part of the database, restored
+/// without dropping it when true or absent.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(rename = "generatedId")]
+    pub generated_id: String,
+    pub status: String,
+    pub method: String,
+    pub timestamp: i64,
+    #[serde(flatten)]
+    pub artifact: ArtifactInfo,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl Manifest {
+    pub async fn new(
+        payload: &BackupPayload,
+        info: &ArtifactInfo,
+        artifact: &Path,
+    ) -> Result<Manifest> {
+        Ok(Manifest {
+            generated_id: payload.generated_id.clone(),
+            status: payload.status.clone(),
+            method: payload.method.clone(),
+            timestamp: payload.timestamp,
+            artifact: info.clone(),
+            size: fs::metadata(artifact).await?.len(),
+            sha256: file_checksum(artifact).await?,
+        })
+    }
+}
+
+/// Artifact read back from the outbox or a storage target, copied under a
+/// local directory
+pub struct FetchedArtifact {
+    pub path: PathBuf,
+    pub timestamp: i64,
+    pub info: ArtifactInfo,
+}
+
+/// Where the newest copy of an artifact is
+enum Source<'a> {
+    Outbox(PathBuf, ArtifactInfo),
+    Local(PathBuf),
+    S3(&'a S3Config, String),
+    Sftp(&'a SftpConfig, PathBuf),
+}
+
+/// Newest artifact of `generated_id` the agent can read back, queued in the
+/// outbox or written to a local, SFTP or S3 (with credentials) target, copied
+/// into `dir`. Targets that cannot be listed are skipped.
+pub async fn fetch_latest(
+    targets: &[StorageConfig],
+    generated_id: &str,
+    dir: &Path,
+) -> Result<Option<FetchedArtifact>> {
+    let mut sources = Vec::new();
+    if let Some((path, payload)) = Outbox::latest_backup(generated_id).await
+        && let Some(info) = payload.artifact
+    {
+        sources.push((payload.timestamp, Source::Outbox(path, info)));
+    }
+    for target in targets {
+        let latest = match target {
+            StorageConfig::Portabase => continue,
+            StorageConfig::Local(cfg) => LocalStorage::new(cfg)
+                .latest(generated_id)
+                .await
+                .map(|l| l.map(|(path, time)| (time, Source::Local(path)))),
+            StorageConfig::S3(cfg) => S3Storage::new(cfg)
+                .latest(generated_id)
+                .await
+                .map(|l| l.map(|(key, time)| (time, Source::S3(cfg, key)))),
+            StorageConfig::Sftp(cfg) => SftpStorage::new(cfg)
+                .latest(generated_id)
+                .await
+                .map(|l| l.map(|(path, time)| (time, Source::Sftp(cfg, path)))),
+        };
+        match latest {
+            Ok(Some((time, source))) => sources.push((time.timestamp(), source)),
+            Ok(None) => {}
+            Err(e) => warn!("Failed to list the backups of {}: {:#}", generated_id, e),
+        }
+    }
+
+    let Some((timestamp, source)) = sources.into_iter().max_by_key(|(time, _)| *time) else {
+        return Ok(None);
+    };
+    let path = dir.join(ARTIFACT_COPY);
+    let info = match source {
+        Source::Outbox(artifact, info) => {
+            fs::copy(&artifact, &path).await?;
+            info
+        }
+        Source::Local(artifact) => {
+            fs::copy(&artifact, &path).await?;
+            LocalStorage::manifest(&artifact).await?.artifact
+        }
+        Source::S3(cfg, key) => S3Storage::new(cfg).fetch(&key, &path).await?.artifact,
+        Source::Sftp(cfg, remote) => SftpStorage::new(cfg).fetch(&remote, &path).await?.artifact,
+    };
+    Ok(Some(FetchedArtifact {
+        path,
+        timestamp,
+        info,
+    }))
+}
+
+/// `<artifact>.json`
+pub fn manifest_name(artifact: &str) -> String {
+    format!("{}.json", artifact)
+}
+
+async fn file_checksum(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finish()))
+}
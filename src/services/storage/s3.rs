@@ -0,0 +1,494 @@
+use crate::core::context::Context;
+use crate::services::backup::{ArtifactInfo, BackupPayload};
+use crate::services::config::S3Config;
+use crate::services::storage::{Manifest, StoredObject, artifact_timestamp, manifest_name};
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Utc};
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sha::sha256;
+use openssl::sign::Signer;
+use reqwest::header::CONTENT_LENGTH;
+use reqwest::{Client, Method, RequestBuilder, Response, Url};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+/// Smallest part of a multipart upload, raised for artifacts that would
+/// otherwise need more than `MAX_PARTS` parts
+const MIN_PART_SIZE: u64 = 16 * 1024 * 1024;
+const MAX_PARTS: u64 = 10_000;
+
+#[derive(Serialize)]
+struct PresignRequest<'a> {
+    #[serde(rename = "generatedId")]
+    generated_id: &'a str,
+    bucket: &'a str,
+    key: &'a str,
+}
+
+#[derive(Deserialize)]
+struct PresignResponse {
+    url: String,
+}
+
+/// Characters left as-is by SigV4 URI encoding, besides alphanumerics
+fn uri_encode(value: &str, keep_slash: bool) -> String {
+    let mut out = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            b'/' if keep_slash => out.push('/'),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+/// Text of the first `<tag>` element of an S3 XML response
+fn xml_value(body: &str, tag: &str) -> Option<String> {
+    xml_values(body, tag).into_iter().next()
+}
+
+/// Text of every `<tag>` element of an S3 XML response
+fn xml_values(body: &str, tag: &str) -> Vec<String> {
+    let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+    let mut values = Vec::new();
+    let mut rest = body;
+    while let Some(start) = rest.find(&open).map(|i| i + open.len())
+        && let Some(end) = rest[start..].find(&close).map(|i| start + i)
+    {
+        values.push(rest[start..end].to_string());
+        rest = &rest[end + close.len()..];
+    }
+    values
+}
+
+async fn check(resp: Response, what: &str) -> Result<Response> {
+    let status = resp.status();
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        anyhow::bail!("S3 {} failed, status: {}, body: {}", what, status, text);
+    }
+    Ok(resp)
+}
+
+fn hmac(key: &[u8], data: &str) -> Result<Vec<u8>> {
+    let pkey = PKey::hmac(key)?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &pkey)?;
+    signer.update(data.as_bytes())?;
+    Ok(signer.sign_to_vec()?)
+}
+
+pub struct S3Storage<'a> {
+    cfg: &'a S3Config,
+    client: Client,
+}
+
+impl<'a> S3Storage<'a> {
+    pub fn new(cfg: &'a S3Config) -> Self {
+        Self {
+            cfg,
+            client: Client::new(),
+        }
+    }
+
+    fn object_url(&self, key: &str) -> Result<Url> {
+        let endpoint = Url::parse(&self.cfg.endpoint)
+            .with_context(|| format!("Invalid S3 endpoint {}", self.cfg.endpoint))?;
+        let host = endpoint
+            .host_str()
+            .with_context(|| format!("S3 endpoint {} has no host", self.cfg.endpoint))?;
+        let authority = match endpoint.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        };
+
+        let url = if self.cfg.path_style {
+            format!(
+                "{}://{}/{}/{}",
+                endpoint.scheme(),
+                authority,
+                self.cfg.bucket,
+                uri_encode(key, true)
+            )
+        } else {
+            format!(
+                "{}://{}.{}/{}",
+                endpoint.scheme(),
+                self.cfg.bucket,
+                authority,
+                uri_encode(key, true)
+            )
+        };
+        Ok(Url::parse(&url)?)
+    }
+
+    /// Sign a request with AWS Signature Version 4
+    fn sign(
+        &self,
+        method: &Method,
+        url: &Url,
+        canonical_query: &str,
+        payload_hash: &str,
+        access_key: &str,
+        secret_key: &str,
+    ) -> Result<Vec<(&'static str, String)>> {
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let mut headers = vec![
+            ("host", host),
+            ("x-amz-content-sha256", payload_hash.to_string()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &self.cfg.session_token {
+            headers.push(("x-amz-security-token", token.expose().to_string()));
+        }
+
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(k, v)| format!("{}:{}\n", k, v.trim()))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(k, _)| *k)
+            .collect::<Vec<_>>()
+            .join(";");
+
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method.as_str(),
+            url.path(),
+            canonical_query,
+            canonical_headers,
+            signed_headers,
+            payload_hash
+        );
+
+        let scope = format!("{}/{}/s3/aws4_request", date, self.cfg.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(sha256(canonical_request.as_bytes()))
+        );
+
+        let k_date = hmac(format!("AWS4{}", secret_key).as_bytes(), &date)?;
+        let k_region = hmac(&k_date, &self.cfg.region)?;
+        let k_service = hmac(&k_region, "s3")?;
+        let k_signing = hmac(&k_service, "aws4_request")?;
+        let signature = hex::encode(hmac(&k_signing, &string_to_sign)?);
+
+        headers.push((
+            "authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                access_key, scope, signed_headers, signature
+            ),
+        ));
+        // reqwest sets the host header itself
+        headers.retain(|(k, _)| *k != "host");
+        Ok(headers)
+    }
+
+    /// Ask the server for a pre-signed PUT URL for `key`
+    async fn presign(&self, ctx: &Context, generated_id: &str, key: &str) -> Result<String> {
+        let url = format!(
+            "{}/api/agent/{}/backup/presign",
+            ctx.edge_key.server_url, ctx.edge_key.agent_id
+        );
+        let body = PresignRequest {
+            generated_id,
+            bucket: &self.cfg.bucket,
+            key,
+        };
+
+        let resp = self.client.post(&url).json(&body).send().await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            anyhow::bail!("Presign request failed, status: {}, body: {}", status, text);
+        }
+        Ok(resp.json::<PresignResponse>().await?.url)
+    }
+
+    /// Signed request on `key`, with `query` as its sub-resource
+    fn signed(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, &str)],
+        body: Vec<u8>,
+        (access_key, secret_key): (&str, &str),
+    ) -> Result<RequestBuilder> {
+        let mut url = self.object_url(key)?;
+        let mut pairs = query
+            .iter()
+            .map(|(k, v)| format!("{}={}", uri_encode(k, false), uri_encode(v, false)))
+            .collect::<Vec<_>>();
+        pairs.sort();
+        let canonical_query = pairs.join("&");
+        if !canonical_query.is_empty() {
+            url.set_query(Some(&canonical_query));
+        }
+
+        let payload_hash = hex::encode(sha256(&body));
+        let headers = self.sign(
+            &method,
+            &url,
+            &canonical_query,
+            &payload_hash,
+            access_key,
+            secret_key,
+        )?;
+        Ok(headers
+            .into_iter()
+            .fold(self.client.request(method, url), |req, (k, v)| {
+                req.header(k, v)
+            })
+            .body(body))
+    }
+
+    /// Upload `artifact` in parts read one at a time, aborting the upload on failure
+    async fn put_multipart(
+        &self,
+        artifact: &Path,
+        key: &str,
+        credentials: (&str, &str),
+    ) -> Result<()> {
+        let request = self.signed(
+            Method::POST,
+            key,
+            &[("uploads", "")],
+            Vec::new(),
+            credentials,
+        )?;
+        let body = check(request.send().await?, "multipart upload creation")
+            .await?
+            .text()
+            .await?;
+        let upload_id = xml_value(&body, "UploadId")
+            .with_context(|| format!("No UploadId in S3 response: {}", body))?;
+
+        let result = self.put_parts(artifact, key, &upload_id, credentials).await;
+        let etags = match result {
+            Ok(etags) => etags,
+            Err(e) => {
+                let abort = self.signed(
+                    Method::DELETE,
+                    key,
+                    &[("uploadId", &upload_id)],
+                    Vec::new(),
+                    credentials,
+                )?;
+                if let Err(abort_err) = check(abort.send().await?, "multipart upload abort").await {
+                    warn!("{}", abort_err);
+                }
+                return Err(e);
+            }
+        };
+
+        let parts: String = etags
+            .iter()
+            .enumerate()
+            .map(|(i, etag)| {
+                format!(
+                    "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                    i + 1,
+                    etag
+                )
+            })
+            .collect();
+        let body = format!(
+            "<CompleteMultipartUpload>{}</CompleteMultipartUpload>",
+            parts
+        );
+        let request = self.signed(
+            Method::POST,
+            key,
+            &[("uploadId", &upload_id)],
+            body.into_bytes(),
+            credentials,
+        )?;
+        // Completion can fail after a 200, with the error in the body
+        let body = check(request.send().await?, "multipart upload completion")
+            .await?
+            .text()
+            .await?;
+        if body.contains("<Error>") {
+            anyhow::bail!("S3 multipart upload completion failed: {}", body);
+        }
+        Ok(())
+    }
+
+    /// Send the parts of `artifact`, returns their ETags in order
+    async fn put_parts(
+        &self,
+        artifact: &Path,
+        key: &str,
+        upload_id: &str,
+        credentials: (&str, &str),
+    ) -> Result<Vec<String>> {
+        let size = fs::metadata(artifact).await?.len();
+        let part_size = MIN_PART_SIZE.max(size.div_ceil(MAX_PARTS));
+        let mut file = fs::File::open(artifact).await?;
+        let mut etags = Vec::new();
+        let mut sent = 0;
+
+        while sent < size || etags.is_empty() {
+            let mut part = Vec::with_capacity(part_size.min(size - sent) as usize);
+            (&mut file).take(part_size).read_to_end(&mut part).await?;
+            if part.is_empty() && !etags.is_empty() {
+                break;
+            }
+            sent += part.len() as u64;
+
+            let number = (etags.len() + 1).to_string();
+            let request = self.signed(
+                Method::PUT,
+                key,
+                &[("partNumber", &number), ("uploadId", upload_id)],
+                part,
+                credentials,
+            )?;
+            let resp = check(request.send().await?, "part upload").await?;
+            let etag = resp
+                .headers()
+                .get("etag")
+                .and_then(|v| v.to_str().ok())
+                .with_context(|| format!("No ETag for part {} of {}", number, key))?;
+            etags.push(etag.to_string());
+        }
+        Ok(etags)
+    }
+
+    fn credentials(&self) -> Option<(&str, &str)> {
+        match (&self.cfg.access_key, &self.cfg.secret_key) {
+            (Some(access_key), Some(secret_key)) => Some((access_key, secret_key.expose())),
+            _ => None,
+        }
+    }
+
+    pub async fn put(
+        &self,
+        ctx: &Context,
+        payload: &BackupPayload,
+        info: &ArtifactInfo,
+        artifact: &Path,
+        key: &str,
+    ) -> Result<StoredObject> {
+        match self.credentials() {
+            Some(credentials) => {
+                self.put_multipart(artifact, key, credentials).await?;
+                let manifest = Manifest::new(payload, info, artifact).await?;
+                let request = self.signed(
+                    Method::PUT,
+                    &manifest_name(key),
+                    &[],
+                    serde_json::to_vec_pretty(&manifest)?,
+                    credentials,
+                )?;
+                check(request.send().await?, "manifest upload").await?;
+            }
+            None => {
+                // A pre-signed URL only allows a single PUT, the file is streamed
+                // into it. Without a manifest, the agent cannot read the copy back.
+                let url = self.presign(ctx, &payload.generated_id, key).await?;
+                let file = fs::File::open(artifact).await?;
+                let size = file.metadata().await?.len();
+                let request = self.client.put(url).header(CONTENT_LENGTH, size).body(file);
+                check(request.send().await?, "upload").await?;
+            }
+        }
+
+        info!("Artifact uploaded to s3://{}/{}", self.cfg.bucket, key);
+        Ok(StoredObject {
+            kind: "s3".into(),
+            location: format!(
+                "{}/{}",
+                self.cfg.endpoint.trim_end_matches('/'),
+                self.cfg.bucket
+            ),
+            key: key.to_string(),
+        })
+    }
+
+    /// Newest copy of `generated_id` with its time, none without credentials
+    /// as listing needs them
+    pub async fn latest(&self, generated_id: &str) -> Result<Option<(String, DateTime<Utc>)>> {
+        let Some(credentials) = self.credentials() else {
+            return Ok(None);
+        };
+        let prefix = format!("{}{}/", self.cfg.prefix, generated_id);
+        let mut latest: Option<(String, DateTime<Utc>)> = None;
+        let mut token: Option<String> = None;
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", prefix.as_str())];
+            if let Some(token) = &token {
+                query.push(("continuation-token", token.as_str()));
+            }
+            let request = self.signed(Method::GET, "", &query, Vec::new(), credentials)?;
+            let body = check(request.send().await?, "listing")
+                .await?
+                .text()
+                .await?;
+
+            for key in xml_values(&body, "Key") {
+                let name = key.rsplit('/').next().unwrap_or_default();
+                if let Some(time) = artifact_timestamp(name)
+                    && latest.as_ref().is_none_or(|(_, newest)| time > *newest)
+                {
+                    latest = Some((key, time));
+                }
+            }
+
+            token = match xml_value(&body, "IsTruncated").as_deref() {
+                Some("true") => xml_value(&body, "NextContinuationToken"),
+                _ => None,
+            };
+            if token.is_none() {
+                return Ok(latest);
+            }
+        }
+    }
+
+    /// Download the artifact at `key` to `dest`, returns its manifest
+    pub async fn fetch(&self, key: &str, dest: &Path) -> Result<Manifest> {
+        let credentials = self
+            .credentials()
+            .context("S3 credentials are required to download artifacts")?;
+
+        let request = self.signed(Method::GET, key, &[], Vec::new(), credentials)?;
+        let mut resp = check(request.send().await?, "download").await?;
+        let mut file = fs::File::create(dest).await?;
+        while let Some(chunk) = resp.chunk().await? {
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+
+        let request = self.signed(
+            Method::GET,
+            &manifest_name(key),
+            &[],
+            Vec::new(),
+            credentials,
+        )?;
+        let manifest = check(request.send().await?, "manifest download")
+            .await?
+            .bytes()
+            .await?;
+        Ok(serde_json::from_slice(&manifest)?)
+    }
+}
@@ -0,0 +1,303 @@
+use crate::services::backup::{ArtifactInfo, BackupPayload};
+use crate::services::config::SftpConfig;
+use crate::services::storage::{Manifest, StoredObject, artifact_timestamp, manifest_name};
+use crate::utils::retention::prunable;
+use crate::utils::secret::Secret;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use ssh2::{CheckResult, KnownHostFileKind, Session, Sftp};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+fn connect(cfg: &SftpConfig) -> Result<Sftp> {
+    let tcp = TcpStream::connect((cfg.host.as_str(), cfg.port))
+        .with_context(|| format!("Failed to connect to SFTP server {}:{}", cfg.host, cfg.port))?;
+
+    let mut session = Session::new()?;
+    session.set_tcp_stream(tcp);
+    session.handshake()?;
+
+    match &cfg.known_hosts {
+        Some(path) => {
+            let mut known_hosts = session.known_hosts()?;
+            known_hosts
+                .read_file(Path::new(path), KnownHostFileKind::OpenSSH)
+                .with_context(|| format!("Failed to read known_hosts file {}", path))?;
+            let (key, _) = session.host_key().context("SFTP server sent no host key")?;
+            match known_hosts.check_port(&cfg.host, cfg.port, key) {
+                CheckResult::Match => {}
+                CheckResult::Mismatch => {
+                    anyhow::bail!("Host key mismatch for SFTP server {}", cfg.host)
+                }
+                CheckResult::NotFound => {
+                    anyhow::bail!("SFTP server {} not found in {}", cfg.host, path)
+                }
+                CheckResult::Failure => {
+                    anyhow::bail!("Host key check failed for SFTP server {}", cfg.host)
+                }
+            }
+        }
+        None if cfg.insecure_skip_host_key_check => warn!(
+            "No known_hosts configured, SFTP server {} key is not verified",
+            cfg.host
+        ),
+        None => anyhow::bail!(
+            "No known_hosts configured for SFTP server {}, refusing to connect",
+            cfg.host
+        ),
+    }
+
+    session
+        .userauth_pubkey_file(
+            &cfg.username,
+            None,
+            Path::new(&cfg.private_key),
+            cfg.passphrase.as_ref().map(Secret::expose),
+        )
+        .with_context(|| format!("SFTP authentication failed for {}", cfg.username))?;
+
+    Ok(session.sftp()?)
+}
+
+/// Create `dir` and its missing parents
+fn mkdir_all(sftp: &Sftp, dir: &Path) -> Result<()> {
+    let mut current = PathBuf::new();
+    for component in dir.components() {
+        current.push(component);
+        if sftp.stat(&current).is_err() {
+            sftp.mkdir(&current, 0o750)
+                .with_context(|| format!("Failed to create remote directory {:?}", current))?;
+        }
+    }
+    Ok(())
+}
+
+/// Write to a temporary name then rename, so readers never see a partial file
+fn upload(
+    sftp: &Sftp,
+    dest: &Path,
+    write: impl FnOnce(&mut ssh2::File) -> Result<()>,
+) -> Result<()> {
+    let mut tmp_name = dest.as_os_str().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = PathBuf::from(tmp_name);
+
+    let mut remote = sftp
+        .create(&tmp)
+        .with_context(|| format!("Failed to create remote file {:?}", tmp))?;
+    write(&mut remote)?;
+    remote.flush()?;
+    drop(remote);
+
+    sftp.rename(&tmp, dest, None)
+        .with_context(|| format!("Failed to rename {:?} to {:?}", tmp, dest))?;
+    Ok(())
+}
+
+pub struct SftpStorage<'a> {
+    cfg: &'a SftpConfig,
+}
+
+impl<'a> SftpStorage<'a> {
+    pub fn new(cfg: &'a SftpConfig) -> Self {
+        Self { cfg }
+    }
+
+    pub fn location(&self) -> String {
+        format!(
+            "sftp://{}@{}:{}",
+            self.cfg.username, self.cfg.host, self.cfg.port
+        )
+    }
+
+    /// Longest part of `directory` without date placeholders, under which
+    /// every remote copy of `generated_id` lives
+    fn root_dir(&self, generated_id: &str) -> (PathBuf, Vec<String>) {
+        let mut root = PathBuf::new();
+        let mut patterns = Vec::new();
+        for (i, component) in self.cfg.directory.split('/').enumerate() {
+            if component.is_empty() {
+                if i == 0 {
+                    root.push("/");
+                }
+                continue;
+            }
+            let component = component.replace("{generated_id}", generated_id);
+            if patterns.is_empty() && !component.contains('{') {
+                root.push(component);
+            } else {
+                patterns.push(component);
+            }
+        }
+        if root.as_os_str().is_empty() {
+            root.push(".");
+        }
+        (root, patterns)
+    }
+
+    pub fn remote_dir(&self, generated_id: &str, timestamp: i64) -> String {
+        let time = DateTime::<Utc>::from_timestamp(timestamp, 0).unwrap_or_else(Utc::now);
+        self.cfg
+            .directory
+            .replace("{generated_id}", generated_id)
+            .replace("{year}", &time.format("%Y").to_string())
+            .replace("{month}", &time.format("%m").to_string())
+            .replace("{day}", &time.format("%d").to_string())
+    }
+
+    pub async fn put(
+        &self,
+        payload: &BackupPayload,
+        info: &ArtifactInfo,
+        artifact: &Path,
+        key: &str,
+    ) -> Result<StoredObject> {
+        let manifest = serde_json::to_vec_pretty(&Manifest::new(payload, info, artifact).await?)?;
+        let cfg = self.cfg.clone();
+        let artifact = artifact.to_path_buf();
+        let dest = PathBuf::from(key);
+        let (root, patterns) = self.root_dir(&payload.generated_id);
+        let object = StoredObject {
+            kind: "sftp".into(),
+            location: self.location(),
+            key: key.to_string(),
+        };
+
+        tokio::task::spawn_blocking(move || -> Result<StoredObject> {
+            let sftp = connect(&cfg)?;
+            let dir = dest.parent().context("Invalid SFTP destination")?;
+            mkdir_all(&sftp, dir)?;
+
+            upload(&sftp, &dest, |remote| {
+                let mut local = std::fs::File::open(&artifact)?;
+                std::io::copy(&mut local, remote)?;
+                Ok(())
+            })?;
+
+            let mut manifest_name = dest.as_os_str().to_os_string();
+            manifest_name.push(".json");
+            upload(&sftp, Path::new(&manifest_name), |remote| {
+                remote.write_all(&manifest)?;
+                Ok(())
+            })?;
+            info!("Artifact uploaded to {}/{:?}", object.location, dest);
+
+            if !cfg.retention.is_empty()
+                && let Err(e) = prune(&sftp, &root, &patterns, &cfg)
+            {
+                warn!("Remote retention pruning failed in {:?}: {}", root, e);
+            }
+
+            Ok(object)
+        })
+        .await?
+    }
+
+    /// Newest remote copy of `generated_id`, with its time
+    pub async fn latest(&self, generated_id: &str) -> Result<Option<(PathBuf, DateTime<Utc>)>> {
+        let cfg = self.cfg.clone();
+        let (root, patterns) = self.root_dir(generated_id);
+        tokio::task::spawn_blocking(move || {
+            let sftp = connect(&cfg)?;
+            let mut artifacts = Vec::new();
+            collect_artifacts(&sftp, &root, &patterns, &mut artifacts)?;
+            Ok(artifacts.into_iter().max_by_key(|(_, time)| *time))
+        })
+        .await?
+    }
+
+    /// Download the artifact at `remote` to `dest`, returns its manifest
+    pub async fn fetch(&self, remote: &Path, dest: &Path) -> Result<Manifest> {
+        let cfg = self.cfg.clone();
+        let remote = remote.to_path_buf();
+        let dest = dest.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let sftp = connect(&cfg)?;
+            let mut file = sftp
+                .open(&remote)
+                .with_context(|| format!("Failed to open remote file {:?}", remote))?;
+            std::io::copy(&mut file, &mut std::fs::File::create(&dest)?)?;
+
+            let name = manifest_name(&remote.to_string_lossy());
+            let mut manifest = Vec::new();
+            sftp.open(Path::new(&name))
+                .with_context(|| format!("Failed to open remote manifest {}", name))?
+                .read_to_end(&mut manifest)?;
+            Ok(serde_json::from_slice(&manifest)?)
+        })
+        .await?
+    }
+}
+
+/// Whether `name` is `pattern` with its `{year}`, `{month}` and `{day}`
+/// placeholders replaced by digits
+fn matches_template(pattern: &str, name: &str) -> bool {
+    let mut pattern = pattern;
+    let mut name = name;
+    loop {
+        let digits = [("{year}", 4), ("{month}", 2), ("{day}", 2)]
+            .into_iter()
+            .find(|(placeholder, _)| pattern.starts_with(placeholder));
+        if let Some((placeholder, width)) = digits {
+            if name.len() < width || !name[..width].bytes().all(|b| b.is_ascii_digit()) {
+                return false;
+            }
+            pattern = &pattern[placeholder.len()..];
+            name = &name[width..];
+            continue;
+        }
+        let (Some(p), Some(n)) = (pattern.chars().next(), name.chars().next()) else {
+            return pattern.is_empty() && name.is_empty();
+        };
+        if p != n {
+            return false;
+        }
+        pattern = &pattern[p.len_utf8()..];
+        name = &name[n.len_utf8()..];
+    }
+}
+
+/// Artifacts in `dir` and in the subdirectories matching `patterns`, one per level
+fn collect_artifacts(
+    sftp: &Sftp,
+    dir: &Path,
+    patterns: &[String],
+    artifacts: &mut Vec<(PathBuf, DateTime<Utc>)>,
+) -> Result<()> {
+    for (path, stat) in sftp.readdir(dir)? {
+        let Some(name) = path.file_name().map(|n| n.to_string_lossy().to_string()) else {
+            continue;
+        };
+        match patterns.split_first() {
+            Some((pattern, rest)) => {
+                if stat.is_dir() && matches_template(pattern, &name) {
+                    collect_artifacts(sftp, &path, rest, artifacts)?;
+                }
+            }
+            None => {
+                if let Some(time) = artifact_timestamp(&name) {
+                    artifacts.push((path, time));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Remove the remote copies the retention policy no longer keeps, across
+/// every dated directory under `root`
+fn prune(sftp: &Sftp, root: &Path, patterns: &[String], cfg: &SftpConfig) -> Result<()> {
+    let mut artifacts = Vec::new();
+    collect_artifacts(sftp, root, patterns, &mut artifacts)?;
+
+    for path in prunable(&cfg.retention, artifacts) {
+        sftp.unlink(&path)?;
+        let mut manifest = path.as_os_str().to_os_string();
+        manifest.push(".json");
+        sftp.unlink(Path::new(&manifest)).ok();
+        info!("Pruned old remote backup {:?}", path);
+    }
+    Ok(())
+}
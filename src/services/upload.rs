@@ -0,0 +1,373 @@
+use crate::core::context::Context;
+use crate::services::backup::{ArtifactInfo, BackupPayload};
+use crate::services::outbox::DeliveryError;
+use crate::services::storage::StoredObject;
+use crate::settings::CONFIG;
+use crate::utils::metrics;
+use crate::utils::process::cancel_requested;
+use crate::utils::progress::{Phase, Progress};
+use openssl::sha::Sha256;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tracing::{debug, info, warn};
+
+const STATE_FILE: &str = "upload.json";
+const PART_ATTEMPTS: u32 = 3;
+
+pub enum UploadOutcome {
+    Completed,
+    /// The server does not expose the chunked upload API
+    Unsupported,
+    /// The job that produced the artifact was cancelled between two parts
+    Cancelled,
+}
+
+/// Progress of a chunked upload, kept next to the artifact so it survives restarts
+#[derive(Debug, Serialize, Deserialize)]
+struct UploadState {
+    upload_id: String,
+    chunk_size: u64,
+    /// Acknowledged part numbers with their checksum
+    parts: BTreeMap<u64, String>,
+}
+
+#[derive(Serialize)]
+struct InitiateRequest<'a> {
+    #[serde(rename = "generatedId")]
+    generated_id: &'a str,
+    status: &'a str,
+    method: &'a str,
+    aes_key: &'a str,
+    iv: &'a str,
+    extension: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filtered: Option<bool>,
+    size: u64,
+    sha256: String,
+    #[serde(rename = "chunkSize")]
+    chunk_size: u64,
+    parts: u64,
+    storage: &'a [StoredObject],
+}
+
+#[derive(Deserialize)]
+struct InitiateResponse {
+    #[serde(rename = "uploadId")]
+    upload_id: String,
+}
+
+#[derive(Deserialize)]
+struct StatusResponse {
+    parts: Vec<u64>,
+}
+
+#[derive(Serialize)]
+struct CompletedPart<'a> {
+    number: u64,
+    sha256: &'a str,
+}
+
+#[derive(Serialize)]
+struct CompleteRequest<'a> {
+    parts: Vec<CompletedPart<'a>>,
+}
+
+pub struct ChunkedUpload<'a> {
+    client: Client,
+    base_url: String,
+    payload: &'a BackupPayload,
+    info: &'a ArtifactInfo,
+    artifact: &'a Path,
+}
+
+impl<'a> ChunkedUpload<'a> {
+    pub fn new(
+        ctx: &Context,
+        payload: &'a BackupPayload,
+        info: &'a ArtifactInfo,
+        artifact: &'a Path,
+    ) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: format!(
+                "{}/api/agent/{}/backup/uploads",
+                ctx.edge_key.server_url, ctx.edge_key.agent_id
+            ),
+            payload,
+            info,
+            artifact,
+        }
+    }
+
+    fn state_path(&self) -> PathBuf {
+        self.artifact.with_file_name(STATE_FILE)
+    }
+
+    async fn load_state(&self) -> Option<UploadState> {
+        let raw = fs::read(self.state_path()).await.ok()?;
+        serde_json::from_slice(&raw).ok()
+    }
+
+    async fn save_state(&self, state: &UploadState) -> Result<(), DeliveryError> {
+        let raw = serde_json::to_vec(state).map_err(|e| DeliveryError::Transient(e.to_string()))?;
+        fs::write(self.state_path(), raw).await?;
+        Ok(())
+    }
+
+    async fn clear_state(&self) {
+        fs::remove_file(self.state_path()).await.ok();
+    }
+
+    async fn read_part(&self, number: u64, chunk_size: u64) -> Result<Vec<u8>, DeliveryError> {
+        let mut file = fs::File::open(self.artifact).await?;
+        file.seek(std::io::SeekFrom::Start(number * chunk_size))
+            .await?;
+        let mut buf = Vec::with_capacity(chunk_size as usize);
+        file.take(chunk_size).read_to_end(&mut buf).await?;
+        Ok(buf)
+    }
+
+    async fn file_checksum(&self) -> Result<String, DeliveryError> {
+        let mut file = fs::File::open(self.artifact).await?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; 1024 * 1024];
+        loop {
+            let n = file.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        Ok(hex::encode(hasher.finish()))
+    }
+
+    async fn initiate(
+        &self,
+        size: u64,
+        chunk_size: u64,
+    ) -> Result<Option<UploadState>, DeliveryError> {
+        let body = InitiateRequest {
+            generated_id: &self.payload.generated_id,
+            status: &self.payload.status,
+            method: &self.payload.method,
+            aes_key: &self.info.aes_key,
+            iv: &self.info.iv,
+            extension: &self.info.extension,
+            filtered: self.info.filtered,
+            size,
+            sha256: self.file_checksum().await?,
+            chunk_size,
+            parts: size.div_ceil(chunk_size).max(1),
+            storage: &self.payload.objects,
+        };
+
+        let resp = self.client.post(&self.base_url).json(&body).send().await?;
+        let status = resp.status();
+        if matches!(
+            status,
+            StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+        ) {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            return Err(DeliveryError::from_response(status, text));
+        }
+
+        let init: InitiateResponse = resp.json().await?;
+        info!(
+            "Chunked upload {} initiated for DB {} ({} bytes)",
+            init.upload_id, self.payload.generated_id, size
+        );
+        Ok(Some(UploadState {
+            upload_id: init.upload_id,
+            chunk_size,
+            parts: BTreeMap::new(),
+        }))
+    }
+
+    /// Reconcile a saved state with the parts the server actually holds,
+    /// returns false when the server no longer knows the upload
+    async fn refresh(&self, state: &mut UploadState) -> Result<bool, DeliveryError> {
+        let url = format!("{}/{}", self.base_url, state.upload_id);
+        let resp = self.client.get(&url).send().await?;
+        let status = resp.status();
+        if status == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            return Err(DeliveryError::from_response(status, text));
+        }
+
+        let server: StatusResponse = resp.json().await?;
+        state.parts.retain(|n, _| server.parts.contains(n));
+        Ok(true)
+    }
+
+    async fn put_part(
+        &self,
+        upload_id: &str,
+        number: u64,
+        data: Vec<u8>,
+        checksum: &str,
+    ) -> Result<(), DeliveryError> {
+        let url = format!("{}/{}/parts/{}", self.base_url, upload_id, number);
+        let mut last_error = None;
+        let size = data.len() as u64;
+
+        for attempt in 1..=PART_ATTEMPTS {
+            let result = self
+                .client
+                .put(&url)
+                .header("Content-Type", "application/octet-stream")
+                .header("X-Part-Checksum", checksum)
+                .body(data.clone())
+                .send()
+                .await;
+
+            match result {
+                Ok(resp) if resp.status().is_success() => {
+                    metrics::record_upload("portabase", size);
+                    return Ok(());
+                }
+                Ok(resp) => {
+                    let status = resp.status();
+                    let text = resp.text().await.unwrap_or_default();
+                    // A checksum mismatch is reported as a conflict, resend the part
+                    if status == StatusCode::CONFLICT {
+                        last_error = Some(DeliveryError::Transient(format!(
+                            "checksum mismatch on part {}: {}",
+                            number, text
+                        )));
+                    } else {
+                        let err = DeliveryError::from_response(status, text);
+                        if matches!(err, DeliveryError::Rejected(..)) {
+                            return Err(err);
+                        }
+                        last_error = Some(err);
+                    }
+                }
+                Err(e) => last_error = Some(e.into()),
+            }
+
+            warn!(
+                "Upload of part {} for {} failed (attempt {}/{})",
+                number, upload_id, attempt, PART_ATTEMPTS
+            );
+            if attempt < PART_ATTEMPTS {
+                metrics::record_upload_retry("part");
+            }
+            tokio::time::sleep(Duration::from_secs(attempt as u64)).await;
+        }
+
+        Err(last_error.unwrap_or_else(|| DeliveryError::Transient("part upload failed".into())))
+    }
+
+    async fn complete(&self, state: &UploadState) -> Result<(), DeliveryError> {
+        let url = format!("{}/{}/complete", self.base_url, state.upload_id);
+        let body = CompleteRequest {
+            parts: state
+                .parts
+                .iter()
+                .map(|(number, sha256)| CompletedPart {
+                    number: *number,
+                    sha256,
+                })
+                .collect(),
+        };
+
+        let resp = self.client.post(&url).json(&body).send().await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            return Err(DeliveryError::from_response(status, text));
+        }
+        Ok(())
+    }
+
+    /// Upload the artifact part by part, resuming from the last acknowledged part
+    pub async fn run(&self) -> Result<UploadOutcome, DeliveryError> {
+        let size = fs::metadata(self.artifact).await?.len();
+
+        let mut resumed = None;
+        if let Some(mut state) = self.load_state().await
+            && self.refresh(&mut state).await?
+        {
+            info!(
+                "Resuming chunked upload {} at {} acknowledged parts",
+                state.upload_id,
+                state.parts.len()
+            );
+            resumed = Some(state);
+        }
+
+        let mut state = match resumed {
+            Some(state) => state,
+            None => match self.initiate(size, CONFIG.upload_chunk_size).await? {
+                Some(state) => state,
+                None => {
+                    debug!("Server does not support chunked uploads");
+                    return Ok(UploadOutcome::Unsupported);
+                }
+            },
+        };
+        self.save_state(&state).await?;
+
+        let total = size.div_ceil(state.chunk_size).max(1);
+        let progress = Progress::current();
+        progress.phase(Phase::Uploading);
+        // Bytes stored by the server once part `number` is acknowledged
+        let stored = |number: u64| ((number + 1) * state.chunk_size).min(size);
+        progress.bytes(0, Some(size));
+        for number in 0..total {
+            if state.parts.contains_key(&number) {
+                progress.bytes(stored(number), Some(size));
+                continue;
+            }
+            if cancel_requested() {
+                info!("Chunked upload {} stopped, job cancelled", state.upload_id);
+                return Ok(UploadOutcome::Cancelled);
+            }
+
+            let data = self.read_part(number, state.chunk_size).await?;
+            let mut hasher = Sha256::new();
+            hasher.update(&data);
+            let checksum = hex::encode(hasher.finish());
+
+            self.put_part(&state.upload_id, number, data, &checksum)
+                .await?;
+            state.parts.insert(number, checksum);
+            self.save_state(&state).await?;
+            progress.bytes(stored(number), Some(size));
+            debug!(
+                "Part {}/{} of {} acknowledged",
+                number + 1,
+                total,
+                state.upload_id
+            );
+        }
+
+        match self.complete(&state).await {
+            Ok(()) => {
+                self.clear_state().await;
+                info!("Chunked upload {} completed", state.upload_id);
+                Ok(UploadOutcome::Completed)
+            }
+            Err(DeliveryError::Rejected(status, body)) if status == StatusCode::CONFLICT => {
+                // Parts do not add up to the announced checksum, start over on the next attempt
+                self.clear_state().await;
+                Err(DeliveryError::Transient(format!(
+                    "upload {} rejected on completion, status: {}, body: {}",
+                    state.upload_id, status, body
+                )))
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
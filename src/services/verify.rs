@@ -0,0 +1,379 @@
+use crate::core::context::Context;
+use crate::core::executor::{Executor, Job, Priority, Submitted};
+use crate::domain::factory::{DatabaseFactory, Inventory, RestoreScope};
+use crate::services::config::{DatabaseConfig, DatabasesConfig, StorageConfig};
+use crate::services::credentials;
+use crate::services::storage;
+use crate::utils::crypto;
+use anyhow::{Context as _, Result};
+use openssl::symm::Mode;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+use tracing::{error, info, warn};
+
+#[derive(Debug, Serialize)]
+pub struct CountMismatch {
+    pub name: String,
+    pub source: u64,
+    pub restored: u64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct VerifyChecks {
+    #[serde(rename = "sourceObjects")]
+    pub source_objects: usize,
+    #[serde(rename = "restoredObjects")]
+    pub restored_objects: usize,
+    pub missing: Vec<String>,
+    pub unexpected: Vec<String>,
+    pub mismatches: Vec<CountMismatch>,
+}
+
+impl VerifyChecks {
+    /// Counts that differ by at most `tolerance` percent are accepted, and
+    /// objects left out of a filtered dump are not reported as missing
+    fn compare(source: &Inventory, restored: &Inventory, tolerance: f64, filtered: bool) -> Self {
+        let mut checks = VerifyChecks {
+            source_objects: source.len(),
+            restored_objects: restored.len(),
+            ..Default::default()
+        };
+
+        for (name, &count) in source {
+            match restored.get(name) {
+                None if filtered => {}
+                None => checks.missing.push(name.clone()),
+                Some(&restored_count) if !within(count, restored_count, tolerance) => {
+                    checks.mismatches.push(CountMismatch {
+                        name: name.clone(),
+                        source: count,
+                        restored: restored_count,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        checks.unexpected = restored
+            .keys()
+            .filter(|name| !source.contains_key(*name))
+            .cloned()
+            .collect();
+
+        checks
+    }
+
+    fn passed(&self) -> bool {
+        self.missing.is_empty() && self.unexpected.is_empty() && self.mismatches.is_empty()
+    }
+}
+
+/// Whether both entries reach the same server, once their hosts are resolved.
+/// Loopback addresses are all the same server, and hosts that do not resolve
+/// fail the check since the restore could not reach them either.
+async fn same_server(source: &DatabaseConfig, sandbox: &DatabaseConfig) -> Result<bool> {
+    if source.port != sandbox.port {
+        return Ok(false);
+    }
+    let resolve = |db: &DatabaseConfig| {
+        let (host, port) = (db.host.clone(), db.port);
+        async move {
+            tokio::net::lookup_host((host.as_str(), port))
+                .await
+                .with_context(|| format!("Failed to resolve host {}", host))
+                .map(|addrs| addrs.map(|a| a.ip()).collect::<Vec<IpAddr>>())
+        }
+    };
+    let source_ips = resolve(source).await?;
+    let sandbox_ips = resolve(sandbox).await?;
+
+    Ok(source_ips.iter().any(|ip| {
+        sandbox_ips
+            .iter()
+            .any(|other| ip == other || (ip.is_loopback() && other.is_loopback()))
+    }))
+}
+
+fn within(expected: u64, actual: u64, tolerance: f64) -> bool {
+    let diff = expected.abs_diff(actual) as f64;
+    diff * 100.0 <= tolerance * expected.max(actual) as f64
+}
+
+#[derive(Debug, Serialize)]
+pub struct VerifyResult {
+    #[serde(rename = "generatedId")]
+    pub generated_id: String,
+    #[serde(rename = "sandboxId")]
+    pub sandbox_id: String,
+    pub status: String,
+    pub checks: Option<VerifyChecks>,
+    pub error: Option<String>,
+    #[serde(rename = "jobId", skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+}
+
+pub struct VerifyService {
+    ctx: Arc<Context>,
+}
+
+impl VerifyService {
+    pub fn new(ctx: Arc<Context>) -> Self {
+        Self { ctx }
+    }
+
+    pub async fn dispatch(&self, generated_id: &str, config: &DatabasesConfig) {
+        let Some(source) = config.find(generated_id).cloned() else {
+            error!(
+                "Verification requested for unknown database {}",
+                generated_id
+            );
+            return;
+        };
+        let Some(verification) = source.verification.clone() else {
+            error!("No verification configured for database {}", generated_id);
+            return;
+        };
+        let Some(sandbox) = config.find(&verification.sandbox).cloned() else {
+            error!(
+                "Sandbox {} for database {} not found in config",
+                verification.sandbox, generated_id
+            );
+            return;
+        };
+
+        let ctx_clone = self.ctx.clone();
+        let host = source.host.clone();
+        let targets = config.storage_targets();
+        let tolerance = verification.tolerance;
+
+        let job = Job::new(
+            &host,
+            Priority::Automatic,
+            "verify",
+            generated_id,
+            |job_id| async move {
+                match TempDir::new() {
+                    Ok(temp_dir) => {
+                        let tmp_path = temp_dir.path().to_path_buf();
+                        info!("Created temp directory {}", tmp_path.display());
+
+                        let mut result =
+                            VerifyService::run(source, sandbox, &targets, tolerance, &tmp_path)
+                                .await;
+                        result.job_id = Some(job_id);
+                        let service = VerifyService { ctx: ctx_clone };
+                        service.send_result(result).await;
+                        // TempDir is automatically deleted when dropped here
+                    }
+                    Err(e) => error!("Failed to create temp dir: {}", e),
+                }
+            },
+        );
+
+        let service = VerifyService {
+            ctx: self.ctx.clone(),
+        };
+        let (cancelled_id, sandbox_id) = (generated_id.to_string(), verification.sandbox.clone());
+        let job = job.on_cancel(|job_id| async move {
+            service
+                .send_result(VerifyResult {
+                    generated_id: cancelled_id,
+                    sandbox_id,
+                    status: "cancelled".into(),
+                    checks: None,
+                    error: None,
+                    job_id: Some(job_id),
+                })
+                .await;
+        });
+
+        let job_id = job.id.clone();
+        if let Submitted::Queued = Executor::submit(job) {
+            self.send_result(VerifyResult {
+                generated_id: generated_id.to_string(),
+                sandbox_id: verification.sandbox.clone(),
+                status: "queued".into(),
+                checks: None,
+                error: None,
+                job_id: Some(job_id),
+            })
+            .await;
+        }
+    }
+
+    /// Restore the latest stored backup of `source` into `sandbox` and compare
+    /// the restored inventory with the one recorded when it was taken
+    pub async fn run(
+        source: DatabaseConfig,
+        sandbox: DatabaseConfig,
+        targets: &[StorageConfig],
+        tolerance: f64,
+        tmp_path: &Path,
+    ) -> VerifyResult {
+        let generated_id = source.generated_id.clone();
+        let sandbox_id = sandbox.generated_id.clone();
+
+        match VerifyService::verify(source, sandbox, targets, tolerance, tmp_path).await {
+            Ok(checks) => VerifyResult {
+                generated_id,
+                sandbox_id,
+                status: if checks.passed() { "success" } else { "failed" }.into(),
+                checks: Some(checks),
+                error: None,
+                job_id: None,
+            },
+            Err(e) => {
+                error!("Verification of {} failed: {}", generated_id, e);
+                VerifyResult {
+                    generated_id,
+                    sandbox_id,
+                    status: "failed".into(),
+                    checks: None,
+                    error: Some(e.to_string()),
+                    job_id: None,
+                }
+            }
+        }
+    }
+
+    async fn verify(
+        source: DatabaseConfig,
+        sandbox: DatabaseConfig,
+        targets: &[StorageConfig],
+        tolerance: f64,
+        tmp_path: &Path,
+    ) -> Result<VerifyChecks> {
+        if !sandbox.sandbox {
+            anyhow::bail!(
+                "Database {} is not marked as a verification sandbox",
+                sandbox.generated_id
+            );
+        }
+        if sandbox.db_type.as_str() != source.db_type.as_str() {
+            anyhow::bail!(
+                "Sandbox type {} does not match source type {}",
+                sandbox.db_type.as_str(),
+                source.db_type.as_str()
+            );
+        }
+        // Dumps restore into the database name they were taken from, so a
+        // sandbox on the source server would overwrite the source itself.
+        if same_server(&source, &sandbox).await? {
+            anyhow::bail!(
+                "Sandbox {} must not point to the same server as {}",
+                sandbox.generated_id,
+                source.generated_id
+            );
+        }
+
+        let Some(fetched) = storage::fetch_latest(targets, &source.generated_id, tmp_path).await?
+        else {
+            anyhow::bail!(
+                "No backup of {} the agent can read back, verification needs a local, SFTP or S3 storage with credentials",
+                source.generated_id
+            );
+        };
+        let info = fetched.info;
+        let Some(agent_key) = &info.agent_key else {
+            anyhow::bail!(
+                "Latest backup of {} predates its verification and cannot be decrypted by the agent",
+                source.generated_id
+            );
+        };
+        let key =
+            crypto::unseal(&hex::decode(agent_key)?).context("Failed to open artifact key")?;
+        let iv = hex::decode(&info.iv)?;
+
+        let backup_file = tmp_path.join(format!("{}{}", source.generated_id, info.extension));
+        let (artifact, dest) = (fetched.path.clone(), backup_file.clone());
+        tokio::task::spawn_blocking(move || {
+            crypto::aes_file(Mode::Decrypt, &key, &iv, &artifact, &dest)
+        })
+        .await?
+        .context("Failed to decrypt artifact")?;
+        std::fs::remove_file(&fetched.path)?;
+        info!(
+            "Verifying backup of {} taken at {}",
+            source.generated_id, fetched.timestamp
+        );
+
+        let source_inventory = match info.inventory {
+            Some(inventory) => inventory,
+            None => {
+                warn!(
+                    "No inventory recorded with the backup of {}, comparing with the current database",
+                    source.generated_id
+                );
+                let (source, _lease) = credentials::resolve(source.clone()).await?;
+                DatabaseFactory::create_for_backup(source)
+                    .await
+                    .inventory()
+                    .await?
+            }
+        };
+        let (sandbox, _sandbox_lease) = credentials::resolve(sandbox).await?;
+
+        let mut target = sandbox.clone();
+        target.database = source.database.clone();
+
+        let target_db = DatabaseFactory::create_for_restore(target.clone(), &backup_file).await;
+        if !target_db.ping().await.unwrap_or(false) {
+            anyhow::bail!("Sandbox {} is not reachable", sandbox.generated_id);
+        }
+        // The sandbox is disposable, start it from an empty database
+        target_db.restore(&backup_file, RestoreScope::Full).await?;
+
+        let restored_inventory = target_db.inventory().await?;
+        let checks = VerifyChecks::compare(
+            &source_inventory,
+            &restored_inventory,
+            tolerance,
+            info.filtered != Some(false),
+        );
+        if !checks.passed() {
+            warn!(
+                "Verification of {} found {} missing, {} unexpected and {} mismatched objects",
+                source.generated_id,
+                checks.missing.len(),
+                checks.unexpected.len(),
+                checks.mismatches.len()
+            );
+        }
+
+        Ok(checks)
+    }
+
+    pub async fn send_result(&self, result: VerifyResult) {
+        info!(
+            "[VerifyService] DB: {} | Sandbox: {} | Status: {}",
+            result.generated_id, result.sandbox_id, result.status,
+        );
+
+        let client = reqwest::Client::new();
+        let url = format!(
+            "{}/api/agent/{}/verify",
+            self.ctx.edge_key.server_url, self.ctx.edge_key.agent_id
+        );
+
+        match client.post(&url).json(&result).send().await {
+            Ok(resp) => {
+                let status = resp.status();
+                if status.is_success() {
+                    info!("Verification result sent successfully");
+                } else {
+                    let text = resp.text().await.unwrap_or_default(); // consumes resp
+                    error!(
+                        "Verification result failed, status: {}, body: {}",
+                        status, text
+                    );
+                }
+            }
+            Err(e) => {
+                error!("Failed to send verification result: {}", e);
+            }
+        }
+    }
+}
@@ -8,12 +8,26 @@ pub struct Settings {
     pub app_env: String,
     pub app_version: String,
     pub redis_url: String,
+    pub schedule_store: String,
     pub edge_key: String,
     pub databases_config_file: String,
     pub data_path: String,
     pub pooling: usize,
     pub timezone: String,
     pub log: String,
+    pub outbox_max_size: u64,
+    pub upload_chunk_size: u64,
+    pub max_concurrent_jobs: usize,
+    pub max_jobs_per_host: usize,
+    pub backup_timeout: u64,
+    pub restore_timeout: u64,
+    pub query_timeout: u64,
+    /// Seconds between two checks of the databases config file
+    pub config_poll_interval: u64,
+    pub progress_interval: u64,
+    /// Address of the health and metrics server, disabled when unset
+    pub http_addr: Option<String>,
+    pub health_timeout: u64,
 }
 
 impl Settings {
@@ -25,6 +39,82 @@ impl Settings {
             .parse::<usize>()
             .expect("POOLING must be a valid positive integer");
 
+        let outbox_max_size_mb = env::var("OUTBOX_MAX_SIZE_MB")
+            .unwrap_or_else(|_| "10240".to_string())
+            .parse::<u64>()
+            .expect("OUTBOX_MAX_SIZE_MB must be a valid positive integer");
+
+        let upload_chunk_size_mb = env::var("UPLOAD_CHUNK_SIZE_MB")
+            .unwrap_or_else(|_| "8".to_string())
+            .parse::<u64>()
+            .ok()
+            .filter(|size| *size > 0)
+            .expect("UPLOAD_CHUNK_SIZE_MB must be a valid positive integer");
+
+        let max_concurrent_jobs = env::var("MAX_CONCURRENT_JOBS")
+            .unwrap_or_else(|_| "4".to_string())
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .expect("MAX_CONCURRENT_JOBS must be a valid positive integer");
+
+        let max_jobs_per_host = env::var("MAX_JOBS_PER_HOST")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .expect("MAX_JOBS_PER_HOST must be a valid positive integer");
+
+        // Defaults match the age at which a FileLock is considered stale
+        let backup_timeout = env::var("BACKUP_TIMEOUT_SECONDS")
+            .unwrap_or_else(|_| "86400".to_string())
+            .parse::<u64>()
+            .expect("BACKUP_TIMEOUT_SECONDS must be a valid positive integer");
+
+        let restore_timeout = env::var("RESTORE_TIMEOUT_SECONDS")
+            .unwrap_or_else(|_| "86400".to_string())
+            .parse::<u64>()
+            .expect("RESTORE_TIMEOUT_SECONDS must be a valid positive integer");
+
+        let query_timeout = env::var("QUERY_TIMEOUT_SECONDS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse::<u64>()
+            .expect("QUERY_TIMEOUT_SECONDS must be a valid positive integer");
+
+        let config_poll_interval = env::var("CONFIG_POLL_INTERVAL_SECONDS")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse::<u64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .expect("CONFIG_POLL_INTERVAL_SECONDS must be a valid positive integer");
+
+        let progress_interval = env::var("PROGRESS_INTERVAL_SECONDS")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse::<u64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .expect("PROGRESS_INTERVAL_SECONDS must be a valid positive integer");
+
+        let health_timeout = env::var("HEALTH_TIMEOUT_SECONDS")
+            .unwrap_or_else(|_| "300".to_string())
+            .parse::<u64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .expect("HEALTH_TIMEOUT_SECONDS must be a valid positive integer");
+
+        let schedule_store = env::var("SCHEDULE_STORE").unwrap_or_else(|_| "file".into());
+        let available = [
+            "file",
+            #[cfg(feature = "redis")]
+            "redis",
+        ];
+        if !available.contains(&schedule_store.as_str()) {
+            panic!(
+                "SCHEDULE_STORE must be one of {:?} in this build, got '{}'",
+                available, schedule_store
+            );
+        }
+
         let tz = env::var("TZ").unwrap_or_else(|_| "UTC".to_string());
 
         Self {
@@ -32,6 +122,7 @@ impl Settings {
             app_env: env::var("APP_ENV").unwrap_or_else(|_| "development".into()),
             redis_url: env::var("CELERY_BROKER_URL")
                 .unwrap_or_else(|_| "redis://localhost:6379/".into()),
+            schedule_store,
             edge_key: env::var("EDGE_KEY").unwrap_or_default(),
             databases_config_file: env::var("DATABASES_CONFIG_FILE")
                 .unwrap_or_else(|_| "config.json".into()),
@@ -39,6 +130,17 @@ impl Settings {
             pooling: pooling_seconds,
             timezone: tz,
             log: env::var("LOG").unwrap_or_else(|_| "info".into()),
+            outbox_max_size: outbox_max_size_mb * 1024 * 1024,
+            upload_chunk_size: upload_chunk_size_mb * 1024 * 1024,
+            max_concurrent_jobs,
+            max_jobs_per_host,
+            backup_timeout,
+            restore_timeout,
+            query_timeout,
+            config_poll_interval,
+            progress_interval,
+            http_addr: env::var("HTTP_ADDR").ok().filter(|a| !a.is_empty()),
+            health_timeout,
         }
     }
 }
@@ -0,0 +1,60 @@
+use crate::core::context::Context;
+use crate::services::config::ConfigService;
+use crate::services::cron::CronService;
+use crate::settings::CONFIG;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::{error, info, warn};
+
+/// Modification time and size of the config file, `None` while it is missing
+fn fingerprint(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Watch the databases config file and swap in each valid edit. A broken edit
+/// is logged and the last valid config stays in use until the file is fixed.
+pub async fn config_watch_loop() {
+    let ctx = Arc::new(Context::new());
+    let config_service = ConfigService::new(ctx.clone());
+    let mut cron_service = CronService::new(ctx.clone()).await;
+    let path = ConfigService::path();
+    info!("Config watcher started on {}", path);
+
+    let mut last = None;
+    loop {
+        let current = fingerprint(Path::new(&path));
+        if current != last {
+            last = current;
+
+            match config_service.reload() {
+                Ok((config, diff)) if !diff.is_empty() => {
+                    let name = |id: &str| {
+                        config
+                            .find(id)
+                            .map(|c| c.name.clone())
+                            .unwrap_or_default()
+                    };
+                    for id in diff.added.iter() {
+                        info!("Config reloaded | database {} ({}) added", id, name(id));
+                    }
+                    for id in diff.changed.iter() {
+                        info!("Config reloaded | database {} ({}) changed", id, name(id));
+                    }
+                    for id in diff.removed.iter() {
+                        info!("Config reloaded | database {} removed", id);
+                        cron_service.remove(id).await;
+                    }
+                }
+                Ok(_) => info!("Config loaded | no database added, changed or removed"),
+                Err(e) => {
+                    error!("Invalid databases config, keeping the previous one: {}", e);
+                    warn!("Fix {} to apply further changes", path);
+                }
+            }
+        }
+
+        tokio::time::sleep(Duration::from_secs(CONFIG.config_poll_interval)).await;
+    }
+}
@@ -0,0 +1,133 @@
+use crate::settings::CONFIG;
+use crate::utils::health::{self, HealthReport};
+use crate::utils::metrics;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, error, info};
+
+/// Largest request head read, probes send a few hundred bytes
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn text(status: u16, body: &str) -> Self {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: format!("{}\n", body),
+        }
+    }
+
+    fn health(report: HealthReport) -> Self {
+        Response {
+            status: if report.is_ok() { 200 } else { 503 },
+            content_type: "application/json",
+            body: serde_json::to_string(&report).unwrap_or_default(),
+        }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+async fn route(method: &str, path: &str) -> Response {
+    if method != "GET" && method != "HEAD" {
+        return Response::text(405, "method not allowed");
+    }
+    match path {
+        "/healthz" => Response::health(health::health()),
+        "/livez" => Response::health(health::liveness()),
+        "/readyz" => Response::health(health::readiness()),
+        "/metrics" => Response {
+            status: 200,
+            content_type: "text/plain; version=0.0.4; charset=utf-8",
+            body: metrics::render().await,
+        },
+        _ => Response::text(404, "not found"),
+    }
+}
+
+/// Read the request head and answer it, one request per connection
+async fn serve(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 || head.len() + n > MAX_REQUEST_SIZE {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let mut parts = head.lines().next().unwrap_or_default().split_whitespace();
+    let (response, with_body) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => {
+            let path = target.split('?').next().unwrap_or(target);
+            let response = route(method, path).await;
+            debug!("HTTP {} {} {}", method, path, response.status);
+            (response, method != "HEAD")
+        }
+        _ => (Response::text(400, "bad request"), true),
+    };
+
+    let header = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason(response.status),
+        response.content_type,
+        response.body.len()
+    );
+    stream.write_all(header.as_bytes()).await?;
+    if with_body {
+        stream.write_all(response.body.as_bytes()).await?;
+    }
+    stream.shutdown().await
+}
+
+/// Serve the health and metrics endpoints on `HTTP_ADDR`, when set
+pub async fn http_server() {
+    health::mark_started();
+    let Some(addr) = CONFIG.http_addr.as_deref() else {
+        return;
+    };
+
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to listen on {}: {}", addr, e);
+            return;
+        }
+    };
+    info!("HTTP server listening on {}", addr);
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(async move {
+                    match tokio::time::timeout(REQUEST_TIMEOUT, serve(stream)).await {
+                        Ok(Err(e)) => debug!("HTTP connection error: {}", e),
+                        Err(_) => debug!("HTTP request timed out"),
+                        Ok(Ok(())) => {}
+                    }
+                });
+            }
+            Err(e) => error!("Failed to accept HTTP connection: {}", e),
+        }
+    }
+}
@@ -1 +1,4 @@
-pub mod ping;
\ No newline at end of file
+pub mod config;
+pub mod http;
+pub mod ping;
+pub mod outbox;
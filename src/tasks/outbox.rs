@@ -0,0 +1,15 @@
+use crate::core::context::Context;
+use crate::services::outbox::Outbox;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+pub async fn outbox_loop() {
+    let ctx = Arc::new(Context::new());
+    info!("Outbox delivery task started");
+
+    loop {
+        Outbox::flush(&ctx).await;
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    }
+}
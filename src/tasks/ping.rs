@@ -2,6 +2,7 @@ use crate::core::agent::Agent;
 use crate::core::context::Context;
 use crate::settings::CONFIG;
 use crate::utils::common::BackupMethod;
+use crate::utils::health::PING_TICK;
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::{error, info};
@@ -19,6 +20,7 @@ pub async fn ping_server() {
                 e
             );
         }
+        PING_TICK.beat();
 
         tokio::time::sleep(Duration::from_secs(CONFIG.pooling as u64)).await;
     }
@@ -0,0 +1,212 @@
+use crate::services::config::{BlackoutAction, BlackoutPeriod, BlackoutWindow};
+use crate::utils::task_manager::cron::{
+    next_run_timestamp, occurrences_between, parse_timezone, resolve,
+};
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone};
+
+/// Blackout in force at a given time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveBlackout {
+    /// Unix time at which every window covering the instant has closed
+    pub ends_at: i64,
+    pub action: BlackoutAction,
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(value, "%H:%M:%S"))
+        .map_err(|e| format!("invalid time '{}': {}", value, e))
+}
+
+/// End of the daily range `from`..`to` containing `at`, evaluated on the wall clock of `tz`
+fn range_end<Z: TimeZone>(tz: Z, from: NaiveTime, to: NaiveTime, at: i64) -> Option<i64> {
+    let local = DateTime::from_timestamp(at, 0)?
+        .with_timezone(&tz)
+        .naive_local();
+    let time = local.time();
+
+    let inside = if from < to {
+        from <= time && time < to
+    } else {
+        // Spans midnight
+        time >= from || time < to
+    };
+    if !inside || from == to {
+        return None;
+    }
+
+    let mut end = local.date().and_time(to);
+    if end <= local {
+        end += Duration::days(1);
+    }
+    resolve(&tz, end).map(|t| t.timestamp())
+}
+
+impl BlackoutWindow {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(name) = self.timezone.as_deref()
+            && parse_timezone(name).is_none()
+        {
+            return Err(format!("unknown timezone '{}'", name));
+        }
+        match &self.period {
+            BlackoutPeriod::Cron { cron, duration } => {
+                if *duration == 0 {
+                    return Err(format!("window '{}' has no duration", cron));
+                }
+                next_run_timestamp(cron, self.timezone.as_deref())
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }
+            BlackoutPeriod::Range { from, to } => {
+                if parse_time(from)? == parse_time(to)? {
+                    return Err(format!("window '{}-{}' is empty", from, to));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// When this window closes, if `at` falls inside it
+    pub fn ends_at(&self, at: i64) -> Option<i64> {
+        let timezone = self.timezone.as_deref();
+        match &self.period {
+            BlackoutPeriod::Cron { cron, duration } => {
+                let duration = *duration as i64;
+                occurrences_between(cron, timezone, at - duration + 1, at)
+                    .last()
+                    .map(|start| start + duration)
+            }
+            BlackoutPeriod::Range { from, to } => {
+                let (from, to) = (parse_time(from).ok()?, parse_time(to).ok()?);
+                match timezone.and_then(parse_timezone) {
+                    Some(tz) => range_end(tz, from, to, at),
+                    None => range_end(Local, from, to, at),
+                }
+            }
+        }
+    }
+}
+
+/// Combine the windows open at `at`. Skipping wins over deferring and the
+/// blackout lasts until the last of them closes.
+pub fn active<'a>(
+    windows: impl IntoIterator<Item = &'a BlackoutWindow>,
+    at: i64,
+) -> Option<ActiveBlackout> {
+    windows
+        .into_iter()
+        .filter_map(|w| Some((w.ends_at(at)?, w.action)))
+        .reduce(|(end, action), (other_end, other_action)| {
+            let action = if action == BlackoutAction::Skip || other_action == BlackoutAction::Skip {
+                BlackoutAction::Skip
+            } else {
+                BlackoutAction::Defer
+            };
+            (end.max(other_end), action)
+        })
+        .map(|(ends_at, action)| ActiveBlackout { ends_at, action })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn ts(y: i32, m: u32, d: u32, h: u32, min: u32) -> i64 {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0)
+            .unwrap()
+            .timestamp()
+    }
+
+    fn range(from: &str, to: &str, timezone: &str, action: BlackoutAction) -> BlackoutWindow {
+        BlackoutWindow {
+            period: BlackoutPeriod::Range {
+                from: from.into(),
+                to: to.into(),
+            },
+            timezone: Some(timezone.into()),
+            action,
+        }
+    }
+
+    #[test]
+    fn range_across_midnight() {
+        let window = range("22:00", "06:00", "UTC", BlackoutAction::Defer);
+        assert_eq!(
+            window.ends_at(ts(2026, 1, 10, 23, 30)),
+            Some(ts(2026, 1, 11, 6, 0))
+        );
+        assert_eq!(
+            window.ends_at(ts(2026, 1, 11, 3, 0)),
+            Some(ts(2026, 1, 11, 6, 0))
+        );
+        assert_eq!(window.ends_at(ts(2026, 1, 11, 6, 0)), None);
+        assert_eq!(window.ends_at(ts(2026, 1, 11, 12, 0)), None);
+    }
+
+    #[test]
+    fn range_on_dst_days() {
+        // Paris skips 02:00-03:00 on 2026-03-29, 01:30 CET is 00:30 UTC
+        let window = range("01:00", "03:30", "Europe/Paris", BlackoutAction::Defer);
+        assert_eq!(
+            window.ends_at(ts(2026, 3, 29, 0, 30)),
+            Some(ts(2026, 3, 29, 1, 30))
+        );
+
+        // An end inside the gap closes at the first instant after it
+        let window = range("01:00", "02:30", "Europe/Paris", BlackoutAction::Defer);
+        assert_eq!(
+            window.ends_at(ts(2026, 3, 29, 0, 30)),
+            Some(ts(2026, 3, 29, 1, 0))
+        );
+
+        // 02:30 happens twice on 2026-10-25, the window closes at the first
+        let window = range("01:00", "02:30", "Europe/Paris", BlackoutAction::Defer);
+        assert_eq!(
+            window.ends_at(ts(2026, 10, 24, 23, 30)),
+            Some(ts(2026, 10, 25, 0, 30))
+        );
+    }
+
+    #[test]
+    fn cron_window() {
+        let window = BlackoutWindow {
+            period: BlackoutPeriod::Cron {
+                cron: "0 0 23 * * *".into(),
+                duration: 2 * 3600,
+            },
+            timezone: Some("UTC".into()),
+            action: BlackoutAction::Skip,
+        };
+        assert_eq!(
+            window.ends_at(ts(2026, 1, 11, 0, 30)),
+            Some(ts(2026, 1, 11, 1, 0))
+        );
+        assert_eq!(window.ends_at(ts(2026, 1, 11, 1, 0)), None);
+    }
+
+    #[test]
+    fn active_combines_open_windows() {
+        let windows = [
+            range("22:00", "06:00", "UTC", BlackoutAction::Defer),
+            range("23:00", "01:00", "UTC", BlackoutAction::Skip),
+            range("12:00", "13:00", "UTC", BlackoutAction::Skip),
+        ];
+        assert_eq!(
+            active(&windows, ts(2026, 1, 10, 23, 30)),
+            Some(ActiveBlackout {
+                ends_at: ts(2026, 1, 11, 6, 0),
+                action: BlackoutAction::Skip,
+            })
+        );
+        assert_eq!(
+            active(&windows, ts(2026, 1, 11, 2, 0)),
+            Some(ActiveBlackout {
+                ends_at: ts(2026, 1, 11, 6, 0),
+                action: BlackoutAction::Defer,
+            })
+        );
+        assert_eq!(active(&windows, ts(2026, 1, 11, 8, 0)), None);
+    }
+}
@@ -1,14 +1,16 @@
+use std::fmt;
+
 #[derive(Clone, Copy)]
 pub enum BackupMethod {
     Automatic,
     Manual,
 }
 
-impl ToString for BackupMethod {
-    fn to_string(&self) -> String {
+impl fmt::Display for BackupMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            BackupMethod::Automatic => "automatic".into(),
-            BackupMethod::Manual => "manual".into(),
+            BackupMethod::Automatic => f.write_str("automatic"),
+            BackupMethod::Manual => f.write_str("manual"),
         }
     }
 }
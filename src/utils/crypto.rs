@@ -0,0 +1,98 @@
+use crate::settings::CONFIG;
+use anyhow::{Context, Result};
+use once_cell::sync::OnceCell;
+use openssl::encrypt::{Decrypter, Encrypter};
+use openssl::hash::MessageDigest;
+use openssl::pkey::{HasPublic, PKey, PKeyRef, Private};
+use openssl::rsa::{Padding, Rsa};
+use openssl::symm::{Cipher, Crypter, Mode};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+/// Size of the reads while encrypting or decrypting a file
+const BLOCK_SIZE: usize = 1024 * 1024;
+
+/// Key pair of this agent under `DATA_PATH`, created on first use
+const AGENT_KEY_FILE: &str = "agent_key.pem";
+const AGENT_KEY_BITS: u32 = 3072;
+
+static AGENT_KEY: OnceCell<PKey<Private>> = OnceCell::new();
+
+/// AES-256-CBC with PKCS7 padding from `src` into `dest`, one block at a time.
+/// Blocking, returns the size written.
+pub fn aes_file(mode: Mode, key: &[u8], iv: &[u8], src: &Path, dest: &Path) -> Result<u64> {
+    let cipher = Cipher::aes_256_cbc();
+    let mut crypter = Crypter::new(cipher, mode, key, Some(iv))?;
+    crypter.pad(true);
+
+    let mut input = File::open(src).with_context(|| format!("Failed to open {:?}", src))?;
+    let mut output = File::create(dest).with_context(|| format!("Failed to create {:?}", dest))?;
+    let mut buf = vec![0u8; BLOCK_SIZE];
+    let mut out = vec![0u8; BLOCK_SIZE + cipher.block_size()];
+    let mut written = 0;
+    loop {
+        let n = input.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        let count = crypter.update(&buf[..n], &mut out)?;
+        output.write_all(&out[..count])?;
+        written += count as u64;
+    }
+    let count = crypter.finalize(&mut out)?;
+    output.write_all(&out[..count])?;
+    output.flush()?;
+    Ok(written + count as u64)
+}
+
+/// Seal `data` with an RSA public key, OAEP with SHA-256 as the server expects
+pub fn seal<T: HasPublic>(pkey: &PKeyRef<T>, data: &[u8]) -> Result<Vec<u8>> {
+    let mut encrypter = Encrypter::new(pkey)?;
+    encrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+    encrypter.set_rsa_oaep_md(MessageDigest::sha256())?;
+    encrypter.set_rsa_mgf1_md(MessageDigest::sha256())?;
+
+    let mut sealed = vec![0u8; encrypter.encrypt_len(data)?];
+    let len = encrypter.encrypt(data, &mut sealed)?;
+    sealed.truncate(len);
+    Ok(sealed)
+}
+
+/// Private key of the agent. Artifact keys are sealed with it as well when the
+/// database is verified, so that the agent can decrypt its own backups.
+pub fn agent_key() -> Result<&'static PKey<Private>> {
+    AGENT_KEY.get_or_try_init(|| {
+        let path = Path::new(&CONFIG.data_path).join(AGENT_KEY_FILE);
+        if path.exists() {
+            let pem = std::fs::read(&path)
+                .with_context(|| format!("Failed to read agent key {:?}", path))?;
+            return Ok(PKey::private_key_from_pem(&pem)?);
+        }
+
+        let rsa = Rsa::generate(AGENT_KEY_BITS)?;
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+            .with_context(|| format!("Failed to create agent key {:?}", path))?;
+        file.write_all(&rsa.private_key_to_pem()?)?;
+        Ok(PKey::from_rsa(rsa)?)
+    })
+}
+
+/// Open `data` sealed with the public half of `agent_key`
+pub fn unseal(data: &[u8]) -> Result<Vec<u8>> {
+    let pkey = agent_key()?;
+    let mut decrypter = Decrypter::new(pkey)?;
+    decrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+    decrypter.set_rsa_oaep_md(MessageDigest::sha256())?;
+    decrypter.set_rsa_mgf1_md(MessageDigest::sha256())?;
+
+    let mut opened = vec![0u8; decrypter.decrypt_len(data)?];
+    let len = decrypter.decrypt(data, &mut opened)?;
+    opened.truncate(len);
+    Ok(opened)
+}
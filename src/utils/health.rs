@@ -0,0 +1,123 @@
+use crate::settings::CONFIG;
+use crate::utils::edge_key::decode_edge_key;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+
+/// Unix time an event last happened at, 0 until it first does
+pub struct Heartbeat(AtomicI64);
+
+impl Heartbeat {
+    const fn new() -> Self {
+        Heartbeat(AtomicI64::new(0))
+    }
+
+    pub fn beat(&self) {
+        self.0.store(Utc::now().timestamp(), Ordering::SeqCst);
+    }
+
+    pub fn last(&self) -> Option<i64> {
+        let at = self.0.load(Ordering::SeqCst);
+        (at > 0).then_some(at)
+    }
+}
+
+/// Iteration of `ping_server` completed, whatever the server answered
+pub static PING_TICK: Heartbeat = Heartbeat::new();
+/// Iteration of `scheduler_loop` completed
+pub static SCHEDULER_TICK: Heartbeat = Heartbeat::new();
+/// Status call answered by the server
+pub static SERVER_REACHED: Heartbeat = Heartbeat::new();
+/// Whether the scheduler's last read of the schedule store succeeded
+pub static STORE_REACHABLE: AtomicBool = AtomicBool::new(false);
+
+/// Start of the agent, loops get `HEALTH_TIMEOUT_SECONDS` from it for their first tick
+static STARTED: Lazy<i64> = Lazy::new(|| Utc::now().timestamp());
+
+pub fn mark_started() {
+    Lazy::force(&STARTED);
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    pub detail: String,
+}
+
+/// Outcome of the checks of a probe, healthy when all pass
+#[derive(Debug, Serialize)]
+pub struct HealthReport {
+    pub status: &'static str,
+    pub checks: Vec<Check>,
+}
+
+impl HealthReport {
+    fn new(checks: Vec<Check>) -> Self {
+        let status = if checks.iter().all(|c| c.ok) { "ok" } else { "fail" };
+        HealthReport { status, checks }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.status == "ok"
+    }
+}
+
+/// Check that `heartbeat` happened within `HEALTH_TIMEOUT_SECONDS`, counting
+/// from the agent's start when it never did and `grace` is set
+fn recent(name: &'static str, heartbeat: &Heartbeat, grace: bool) -> Check {
+    let now = Utc::now().timestamp();
+    let (ok, detail) = match heartbeat.last() {
+        Some(at) => (
+            now - at <= CONFIG.health_timeout as i64,
+            format!("last {}s ago", now - at),
+        ),
+        None if grace => (
+            now - *STARTED <= CONFIG.health_timeout as i64,
+            format!("none yet, started {}s ago", now - *STARTED),
+        ),
+        None => (false, "never".into()),
+    };
+    Check { name, ok, detail }
+}
+
+/// The process answers
+pub fn health() -> HealthReport {
+    HealthReport::new(Vec::new())
+}
+
+/// The ping and scheduler loops are still ticking
+pub fn liveness() -> HealthReport {
+    HealthReport::new(vec![
+        recent("ping_server", &PING_TICK, true),
+        recent("scheduler_loop", &SCHEDULER_TICK, true),
+    ])
+}
+
+/// The agent can do its work: its key decodes, the server answered lately
+/// and the schedule store is readable
+pub fn readiness() -> HealthReport {
+    let edge_key = match decode_edge_key(&CONFIG.edge_key) {
+        Ok(_) => Check {
+            name: "edge_key",
+            ok: true,
+            detail: "decoded".into(),
+        },
+        Err(e) => Check {
+            name: "edge_key",
+            ok: false,
+            detail: e.to_string(),
+        },
+    };
+    let store = STORE_REACHABLE.load(Ordering::SeqCst);
+    HealthReport::new(vec![
+        edge_key,
+        recent("server", &SERVER_REACHED, false),
+        Check {
+            name: "schedule_store",
+            ok: store,
+            detail: if store { "reachable" } else { "unreachable" }.into(),
+        },
+    ])
+}
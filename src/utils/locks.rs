@@ -23,6 +23,14 @@ impl DbOpLock {
     }
 }
 
+/// Lock found on disk, as reported by the metrics
+pub struct LockHolder {
+    pub id: String,
+    /// Service name written by `acquire`
+    pub service: String,
+    pub age: Duration,
+}
+
 /// File-based lock utility
 pub struct FileLock;
 
@@ -97,6 +105,38 @@ impl FileLock {
         Ok(())
     }
 
+    /// Locks currently held, by this agent or another process sharing the directory
+    pub async fn holders() -> Vec<LockHolder> {
+        let mut holders = Vec::new();
+        let Ok(mut dir) = read_dir(Self::LOCK_DIR).await else {
+            return holders;
+        };
+        while let Ok(Some(entry)) = dir.next_entry().await {
+            let path = entry.path();
+            if path.extension().map(|e| e != "lock").unwrap_or(true) {
+                continue;
+            }
+            let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let contents = tokio::fs::read_to_string(&path).await.unwrap_or_default();
+            let service = contents
+                .lines()
+                .find_map(|l| l.strip_prefix("Service: "))
+                .unwrap_or("unknown");
+            let age = match entry.metadata().await.and_then(|m| m.modified()) {
+                Ok(modified) => SystemTime::now().duration_since(modified).unwrap_or_default(),
+                Err(_) => Duration::ZERO,
+            };
+            holders.push(LockHolder {
+                id: id.to_string(),
+                service: service.to_string(),
+                age,
+            });
+        }
+        holders
+    }
+
     /// Release the file-based lock
     pub async fn release(id: &str) -> Result<()> {
         let path = Self::lock_file_path(id);
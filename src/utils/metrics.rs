@@ -0,0 +1,220 @@
+use crate::utils::locks::FileLock;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+type Labels = Vec<(&'static str, String)>;
+
+/// Series of one metric, keyed by name suffix (`_sum`, `_count`...) and labels
+struct Family {
+    help: &'static str,
+    kind: &'static str,
+    series: BTreeMap<(&'static str, Labels), f64>,
+}
+
+/// Metrics recorded since the agent started, rendered in the Prometheus text format
+static REGISTRY: Lazy<Mutex<BTreeMap<&'static str, Family>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+fn update(
+    name: &'static str,
+    help: &'static str,
+    kind: &'static str,
+    suffix: &'static str,
+    labels: Labels,
+    f: impl FnOnce(&mut f64),
+) {
+    let mut registry = REGISTRY.lock().unwrap();
+    let family = registry.entry(name).or_insert_with(|| Family {
+        help,
+        kind,
+        series: BTreeMap::new(),
+    });
+    f(family.series.entry((suffix, labels)).or_insert(0.0));
+}
+
+fn inc(name: &'static str, help: &'static str, labels: Labels, by: f64) {
+    update(name, help, "counter", "", labels, |v| *v += by);
+}
+
+fn set(name: &'static str, help: &'static str, labels: Labels, value: f64) {
+    update(name, help, "gauge", "", labels, |v| *v = value);
+}
+
+fn observe(name: &'static str, help: &'static str, labels: Labels, value: f64) {
+    update(name, help, "summary", "_sum", labels.clone(), |v| *v += value);
+    update(name, help, "summary", "_count", labels, |v| *v += 1.0);
+}
+
+fn id(generated_id: &str) -> Labels {
+    vec![("generated_id", generated_id.to_string())]
+}
+
+/// Backup job finished with `status`
+pub fn record_backup(generated_id: &str, status: &str, duration: Duration) {
+    let mut labels = id(generated_id);
+    labels.push(("status", status.to_string()));
+    inc(
+        "portabase_backups_total",
+        "Backup jobs by database and status",
+        labels,
+        1.0,
+    );
+    observe(
+        "portabase_backup_duration_seconds",
+        "Time spent in backup jobs, dump included",
+        id(generated_id),
+        duration.as_secs_f64(),
+    );
+    if status == "success" {
+        set(
+            "portabase_backup_last_success_timestamp_seconds",
+            "Unix time of the last successful backup",
+            id(generated_id),
+            Utc::now().timestamp() as f64,
+        );
+    }
+}
+
+/// Restore job finished with `status`
+pub fn record_restore(generated_id: &str, status: &str, duration: Duration) {
+    let mut labels = id(generated_id);
+    labels.push(("status", status.to_string()));
+    inc(
+        "portabase_restores_total",
+        "Restore jobs by database and status",
+        labels,
+        1.0,
+    );
+    observe(
+        "portabase_restore_duration_seconds",
+        "Time spent in restore jobs, download included",
+        id(generated_id),
+        duration.as_secs_f64(),
+    );
+    if status == "success" {
+        set(
+            "portabase_restore_last_success_timestamp_seconds",
+            "Unix time of the last successful restore",
+            id(generated_id),
+            Utc::now().timestamp() as f64,
+        );
+    }
+}
+
+/// Size of the last encrypted backup artifact
+pub fn record_artifact(generated_id: &str, bytes: u64) {
+    set(
+        "portabase_backup_artifact_bytes",
+        "Size of the last encrypted backup artifact",
+        id(generated_id),
+        bytes as f64,
+    );
+}
+
+/// Artifact bytes written to a storage target, `portabase` for the server
+pub fn record_upload(target: &str, bytes: u64) {
+    inc(
+        "portabase_upload_bytes_total",
+        "Artifact bytes uploaded by storage target",
+        vec![("target", target.to_string())],
+        bytes as f64,
+    );
+}
+
+/// Upload attempt that failed and is tried again, `part` for a chunk of a
+/// chunked upload and `delivery` for an outbox entry
+pub fn record_upload_retry(kind: &str) {
+    inc(
+        "portabase_upload_retries_total",
+        "Failed upload attempts retried, by kind",
+        vec![("kind", kind.to_string())],
+        1.0,
+    );
+}
+
+/// Scheduled task started `lag` seconds after its due time
+pub fn record_scheduler_lag(task: &str, lag: i64) {
+    set(
+        "portabase_scheduler_lag_seconds",
+        "Delay between the scheduled and actual start of the last run of a task",
+        vec![("task", task.to_string())],
+        lag.max(0) as f64,
+    );
+}
+
+/// Status call to the server, successful or not
+pub fn record_ping(duration: Duration, ok: bool) {
+    if ok {
+        set(
+            "portabase_server_ping_duration_seconds",
+            "Duration of the last successful status call",
+            Vec::new(),
+            duration.as_secs_f64(),
+        );
+    } else {
+        inc(
+            "portabase_server_ping_failures_total",
+            "Failed status calls",
+            Vec::new(),
+            1.0,
+        );
+    }
+    inc(
+        "portabase_server_pings_total",
+        "Status calls to the server",
+        Vec::new(),
+        1.0,
+    );
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn write_family(out: &mut String, name: &str, family: &Family) {
+    let _ = writeln!(out, "# HELP {} {}", name, family.help);
+    let _ = writeln!(out, "# TYPE {} {}", name, family.kind);
+    for ((suffix, labels), value) in family.series.iter() {
+        let labels = labels
+            .iter()
+            .map(|(k, v)| format!("{}=\"{}\"", k, escape(v)))
+            .collect::<Vec<_>>()
+            .join(",");
+        if labels.is_empty() {
+            let _ = writeln!(out, "{}{} {}", name, suffix, value);
+        } else {
+            let _ = writeln!(out, "{}{}{{{}}} {}", name, suffix, labels, value);
+        }
+    }
+}
+
+/// Every metric in the Prometheus text format, lock holders read at call time
+pub async fn render() -> String {
+    let mut locks = Family {
+        help: "Operation lock held on a database, with its age in seconds",
+        kind: "gauge",
+        series: BTreeMap::new(),
+    };
+    for holder in FileLock::holders().await {
+        let labels = vec![
+            ("generated_id", holder.id),
+            ("service", holder.service),
+        ];
+        locks.series.insert(("", labels), holder.age.as_secs_f64());
+    }
+
+    let mut out = String::new();
+    let registry = REGISTRY.lock().unwrap();
+    for (name, family) in registry.iter() {
+        write_family(&mut out, name, family);
+    }
+    write_family(&mut out, "portabase_lock_age_seconds", &locks);
+    out
+}
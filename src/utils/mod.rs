@@ -1,8 +1,17 @@
+pub mod blackout;
 pub mod common;
+pub mod crypto;
 pub mod edge_key;
+#[cfg(feature = "redis")]
 pub mod redis_client;
 pub mod task_manager;
 pub mod text;
 pub mod file;
+pub mod health;
 pub mod locks;
+pub mod process;
+pub mod progress;
 pub mod logging;
+pub mod metrics;
+pub mod retention;
+pub mod secret;
@@ -0,0 +1,204 @@
+use crate::utils::progress::Progress;
+use std::future::Future;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, ChildStderr, Command, ExitStatus, Output, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+tokio::task_local! {
+    /// Cancellation flag of the executor job running the current task
+    pub static JOB_CANCEL: Arc<AtomicBool>;
+}
+
+#[derive(Debug, Error)]
+pub enum ProcessError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("timed out after {0}s")]
+    TimedOut(u64),
+    #[error("cancelled")]
+    Cancelled,
+}
+
+/// Whether an operation failed because its job was cancelled
+pub fn was_cancelled(e: &anyhow::Error) -> bool {
+    e.chain()
+        .any(|c| matches!(c.downcast_ref::<ProcessError>(), Some(ProcessError::Cancelled)))
+}
+
+/// Whether the executor job running the current task was asked to stop
+pub fn cancel_requested() -> bool {
+    JOB_CANCEL
+        .try_with(|c| c.load(Ordering::SeqCst))
+        .unwrap_or(false)
+}
+
+/// Await `future` for at most `timeout`, for client calls that do not go
+/// through an external command
+pub async fn within<T>(
+    timeout: Option<Duration>,
+    future: impl Future<Output = anyhow::Result<T>>,
+) -> anyhow::Result<T> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future)
+            .await
+            .map_err(|_| ProcessError::TimedOut(timeout.as_secs()))?,
+        None => future.await,
+    }
+}
+
+/// Deadline and cancellation applied to the external commands of one operation.
+/// Commands run in their own process group, killed as a whole when either fires.
+/// Their stderr is fed line by line to the job's progress.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessControl {
+    cancel: Option<Arc<AtomicBool>>,
+    progress: Progress,
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+}
+
+impl ProcessControl {
+    /// Control for an operation of the current job, `timeout` counts from now.
+    /// Must be created on the job's task, before moving to a blocking thread.
+    pub fn current(timeout: Option<Duration>) -> Self {
+        ProcessControl {
+            cancel: JOB_CANCEL.try_with(Arc::clone).ok(),
+            progress: Progress::current(),
+            timeout,
+            deadline: timeout.map(|t| Instant::now() + t),
+        }
+    }
+
+    /// Spawn `cmd` as the leader of a new process group
+    pub fn spawn(&self, cmd: &mut Command) -> io::Result<Child> {
+        cmd.process_group(0).spawn()
+    }
+
+    /// Like `Command::status`, within the limits
+    pub fn status(&self, cmd: &mut Command) -> Result<ExitStatus, ProcessError> {
+        let mut child = self.spawn(cmd.stderr(Stdio::piped()))?;
+        let pid = child.id();
+        let stderr = self.follow(child.stderr.take());
+        let status = self.supervise(pid, || child.wait());
+        let _ = stderr.join();
+        status
+    }
+
+    /// Like `Command::output`, within the limits
+    pub fn output(&self, cmd: &mut Command) -> Result<Output, ProcessError> {
+        let mut child = self.spawn(
+            cmd.stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped()),
+        )?;
+        let pid = child.id();
+        let stderr = self.follow(child.stderr.take());
+        let mut output = self.supervise(pid, || child.wait_with_output())?;
+        output.stderr = stderr.join().unwrap_or_default();
+        Ok(output)
+    }
+
+    /// Like `output`, writing `input` to the stdin of the process first
+    pub fn output_with_input(
+        &self,
+        cmd: &mut Command,
+        input: &[u8],
+    ) -> Result<Output, ProcessError> {
+        let mut child = self.spawn(
+            cmd.stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped()),
+        )?;
+        let pid = child.id();
+        let stdin = child.stdin.take();
+        let stderr = self.follow(child.stderr.take());
+        let mut output = self.supervise(pid, || {
+            if let Some(mut stdin) = stdin {
+                stdin.write_all(input)?;
+            }
+            child.wait_with_output()
+        })?;
+        output.stderr = stderr.join().unwrap_or_default();
+        Ok(output)
+    }
+
+    /// Read `stderr` until it closes, feeding each line to the progress.
+    /// Returns everything read, for error messages.
+    fn follow(&self, stderr: Option<ChildStderr>) -> thread::JoinHandle<Vec<u8>> {
+        let progress = self.progress.clone();
+        thread::spawn(move || {
+            let mut captured = Vec::new();
+            let Some(stderr) = stderr else {
+                return captured;
+            };
+            let mut reader = BufReader::new(stderr);
+            let mut line = Vec::new();
+            while matches!(reader.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+                let text = String::from_utf8_lossy(&line);
+                debug!("{}", text.trim_end());
+                progress.line(&text);
+                captured.append(&mut line);
+            }
+            captured
+        })
+    }
+
+    /// Run `wait`, which blocks on the process group led by `pid`, and kill
+    /// the group if the deadline passes or the job is cancelled meanwhile
+    pub fn supervise<T>(
+        &self,
+        pid: u32,
+        wait: impl FnOnce() -> io::Result<T>,
+    ) -> Result<T, ProcessError> {
+        if self.cancel.is_none() && self.deadline.is_none() {
+            return Ok(wait()?);
+        }
+
+        let done = Arc::new(AtomicBool::new(false));
+        let watchdog = {
+            let done = done.clone();
+            let control = self.clone();
+            thread::spawn(move || {
+                while !done.load(Ordering::SeqCst) {
+                    if let Some(reason) = control.expired() {
+                        warn!("Killing process group {}: {}", pid, reason);
+                        // SAFETY: plain syscall, the group was created by `spawn`
+                        unsafe {
+                            libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+                        }
+                        return Some(reason);
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                }
+                None
+            })
+        };
+
+        let result = wait();
+        done.store(true, Ordering::SeqCst);
+        match watchdog.join() {
+            Ok(Some(reason)) => Err(reason),
+            _ => Ok(result?),
+        }
+    }
+
+    fn expired(&self) -> Option<ProcessError> {
+        if self.cancel.as_ref().is_some_and(|c| c.load(Ordering::SeqCst)) {
+            return Some(ProcessError::Cancelled);
+        }
+        match (self.deadline, self.timeout) {
+            (Some(deadline), Some(timeout)) if Instant::now() >= deadline => {
+                Some(ProcessError::TimedOut(timeout.as_secs()))
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+tokio::task_local! {
+    /// Progress of the executor job running the current task
+    pub static JOB_PROGRESS: Progress;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    Starting,
+    Dumping,
+    Archiving,
+    Encrypting,
+    Uploading,
+    Downloading,
+    Restoring,
+}
+
+/// Progress as reported to the server
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgressSnapshot {
+    pub phase: Phase,
+    /// Table or collection being processed, as printed by the dump tool
+    pub item: Option<String>,
+    pub bytes: u64,
+    #[serde(rename = "totalBytes")]
+    pub total_bytes: Option<u64>,
+    pub percent: Option<f64>,
+}
+
+#[derive(Debug)]
+struct State {
+    phase: Phase,
+    item: Option<String>,
+    bytes: u64,
+    total_bytes: Option<u64>,
+    percent: Option<f64>,
+    /// Directory whose size is reported as `bytes` while set
+    watch: Option<PathBuf>,
+}
+
+/// Shared progress of one job. Every method is a no-op on the default
+/// handle, so code running outside a job can report unconditionally.
+#[derive(Debug, Clone, Default)]
+pub struct Progress(Option<Arc<Mutex<State>>>);
+
+impl Progress {
+    pub fn new() -> Self {
+        Progress(Some(Arc::new(Mutex::new(State {
+            phase: Phase::Starting,
+            item: None,
+            bytes: 0,
+            total_bytes: None,
+            percent: None,
+            watch: None,
+        }))))
+    }
+
+    /// Progress of the current job, a no-op handle outside of one
+    pub fn current() -> Self {
+        JOB_PROGRESS.try_with(Clone::clone).unwrap_or_default()
+    }
+
+    fn update(&self, f: impl FnOnce(&mut State)) {
+        if let Some(state) = &self.0 {
+            f(&mut state.lock().unwrap());
+        }
+    }
+
+    /// Enter `phase`, forgetting what was reported during the previous one
+    pub fn phase(&self, phase: Phase) {
+        self.update(|s| {
+            if s.phase != phase {
+                s.phase = phase;
+                s.item = None;
+                s.bytes = 0;
+                s.total_bytes = None;
+                s.percent = None;
+                s.watch = None;
+            }
+        });
+    }
+
+    /// Report the size of `dir` as the bytes written so far
+    pub fn watch(&self, dir: &Path) {
+        self.update(|s| s.watch = Some(dir.to_path_buf()));
+    }
+
+    pub fn bytes(&self, bytes: u64, total: Option<u64>) {
+        self.update(|s| {
+            s.watch = None;
+            s.bytes = bytes;
+            s.total_bytes = total;
+            s.percent = total
+                .filter(|t| *t > 0)
+                .map(|t| (bytes as f64 * 100.0 / t as f64).min(100.0));
+        });
+    }
+
+    /// Feed a line of dump or restore tool output
+    pub fn line(&self, line: &str) {
+        if let Some((item, percent)) = parse_line(line) {
+            self.update(|s| {
+                s.item = Some(item);
+                if percent.is_some() {
+                    s.percent = percent;
+                }
+            });
+        }
+    }
+
+    /// Current progress. A watched directory is measured on a blocking
+    /// thread once the state is released, so the job never waits on the walk.
+    pub async fn snapshot(&self) -> Option<ProgressSnapshot> {
+        let (mut snapshot, watch) = {
+            let state = self.0.as_ref()?.lock().unwrap();
+            let snapshot = ProgressSnapshot {
+                phase: state.phase,
+                item: state.item.clone(),
+                bytes: state.bytes,
+                total_bytes: state.total_bytes,
+                percent: state.percent,
+            };
+            (snapshot, state.watch.clone())
+        };
+        if let Some(dir) = watch {
+            snapshot.bytes = tokio::task::spawn_blocking(move || dir_size(&dir))
+                .await
+                .unwrap_or(0);
+        }
+        Some(snapshot)
+    }
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&entry.path()),
+            Ok(_) => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Extract the table or collection being processed, and the tool's own
+/// completion estimate when it prints one, from a line of verbose output of
+/// pg_dump/pg_restore, mysqldump or mongodump/mongorestore
+fn parse_line(line: &str) -> Option<(String, Option<f64>)> {
+    let line = line.trim();
+
+    // pg_dump: dumping contents of table "public.users"
+    // pg_restore: processing data for table "public.users"
+    for marker in ["dumping contents of table ", "processing data for table "] {
+        if let Some(rest) = line.split_once(marker).map(|(_, r)| r) {
+            return Some((rest.trim_matches('"').to_string(), None));
+        }
+    }
+
+    // -- Retrieving table structure for table `users`...
+    // -- Sending SELECT query...  (no table name, ignored)
+    if let Some(rest) = line.strip_prefix("-- Retrieving table structure for table ") {
+        let table = rest.trim_end_matches("...").trim_matches('`');
+        return Some((table.to_string(), None));
+    }
+
+    // mongodump/mongorestore:
+    //   <timestamp>\twriting app.users to archive on stdout
+    //   <timestamp>\trestoring app.users from archive
+    //   <timestamp>\t[####....................]  app.users  101/1000  (10.1%)
+    let message = line.split_once('\t').map(|(_, m)| m).unwrap_or(line).trim();
+    for marker in ["writing ", "restoring "] {
+        if let Some(rest) = message.strip_prefix(marker)
+            && let Some(name) = rest.split_whitespace().next().filter(|n| n.contains('.'))
+        {
+            return Some((name.to_string(), None));
+        }
+    }
+    if message.starts_with('[') {
+        let mut fields = message.split_whitespace().skip_while(|f| !f.ends_with(']')).skip(1);
+        let name = fields.next()?;
+        let percent = fields
+            .last()
+            .and_then(|p| p.strip_prefix('(')?.strip_suffix("%)")?.parse::<f64>().ok());
+        return Some((name.to_string(), percent));
+    }
+
+    None
+}
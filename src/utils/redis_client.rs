@@ -1,12 +1,7 @@
 use redis::{aio::MultiplexedConnection, Client};
-use crate::settings::CONFIG;
 
-pub async fn redis_connection() -> MultiplexedConnection {
-    let client = Client::open(CONFIG.redis_url.clone())
-        .expect("Invalid Redis URL");
+pub async fn redis_connection(url: &str) -> redis::RedisResult<MultiplexedConnection> {
+    let client = Client::open(url)?;
 
-    client
-        .get_multiplexed_async_connection()
-        .await
-        .expect("Failed to connect to Redis")
+    client.get_multiplexed_async_connection().await
 }
@@ -0,0 +1,120 @@
+use crate::services::config::RetentionPolicy;
+use chrono::{DateTime, Datelike, Utc};
+use std::collections::HashSet;
+
+impl RetentionPolicy {
+    pub fn is_empty(&self) -> bool {
+        self.keep_last == 0
+            && self.keep_daily == 0
+            && self.keep_weekly == 0
+            && self.keep_monthly == 0
+    }
+}
+
+/// Keep the newest item of each of the `count` most recent periods
+fn keep_per_period<T, K: Eq + std::hash::Hash>(
+    items: &[(T, DateTime<Utc>)],
+    count: usize,
+    period: impl Fn(&DateTime<Utc>) -> K,
+    keep: &mut HashSet<usize>,
+) {
+    let mut seen = HashSet::new();
+    for (idx, (_, time)) in items.iter().enumerate() {
+        if seen.len() >= count {
+            break;
+        }
+        if seen.insert(period(time)) {
+            keep.insert(idx);
+        }
+    }
+}
+
+/// Items the policy no longer keeps, `items` in any order
+pub fn prunable<T>(policy: &RetentionPolicy, mut items: Vec<(T, DateTime<Utc>)>) -> Vec<T> {
+    if policy.is_empty() {
+        return Vec::new();
+    }
+
+    items.sort_by_key(|item| std::cmp::Reverse(item.1));
+
+    let mut keep: HashSet<usize> = (0..policy.keep_last.min(items.len())).collect();
+    keep_per_period(&items, policy.keep_daily, |t| t.date_naive(), &mut keep);
+    keep_per_period(&items, policy.keep_weekly, |t| t.iso_week(), &mut keep);
+    keep_per_period(
+        &items,
+        policy.keep_monthly,
+        |t| (t.year(), t.month()),
+        &mut keep,
+    );
+
+    items
+        .into_iter()
+        .enumerate()
+        .filter(|(idx, _)| !keep.contains(idx))
+        .map(|(_, (item, _))| item)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(y: i32, m: u32, d: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, 12, 0, 0).unwrap()
+    }
+
+    fn policy(last: usize, daily: usize, weekly: usize, monthly: usize) -> RetentionPolicy {
+        RetentionPolicy {
+            keep_last: last,
+            keep_daily: daily,
+            keep_weekly: weekly,
+            keep_monthly: monthly,
+        }
+    }
+
+    #[test]
+    fn empty_policy_prunes_nothing() {
+        let items = vec![("a", at(2025, 1, 1)), ("b", at(2024, 1, 1))];
+        assert!(prunable(&policy(0, 0, 0, 0), items).is_empty());
+    }
+
+    #[test]
+    fn iso_weeks_span_the_year_boundary() {
+        // Monday 2024-12-30 and Wednesday 2025-01-01 are both in ISO week 1
+        // of 2025, Sunday 2024-12-29 ends week 52 of 2024
+        let items = vec![
+            ("dec29", at(2024, 12, 29)),
+            ("jan01", at(2025, 1, 1)),
+            ("dec30", at(2024, 12, 30)),
+        ];
+        assert_eq!(prunable(&policy(0, 0, 2, 0), items), vec!["dec30"]);
+    }
+
+    #[test]
+    fn months_do_not_merge_across_years() {
+        let items = vec![
+            ("jan01", at(2025, 1, 1)),
+            ("dec31", at(2024, 12, 31)),
+            ("dec01", at(2024, 12, 1)),
+            ("jan02", at(2025, 1, 2)),
+        ];
+        let mut pruned = prunable(&policy(0, 0, 0, 2), items);
+        pruned.sort();
+        assert_eq!(pruned, vec!["dec01", "jan01"]);
+    }
+
+    #[test]
+    fn rules_add_up() {
+        let items = vec![
+            ("d1a", at(2025, 3, 10)),
+            ("d1b", Utc.with_ymd_and_hms(2025, 3, 10, 8, 0, 0).unwrap()),
+            ("d2", at(2025, 3, 9)),
+            ("d3", at(2025, 3, 8)),
+        ];
+        // keep_last keeps the newest, keep_daily the newest of two more days
+        let mut pruned = prunable(&policy(1, 2, 0, 0), items);
+        pruned.sort();
+        assert_eq!(pruned, vec!["d1b", "d3"]);
+    }
+}
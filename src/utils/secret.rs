@@ -0,0 +1,76 @@
+use serde::Deserialize;
+use std::env;
+use std::fmt;
+
+/// Credential read from the config, never printed by `Debug`
+#[derive(Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Secret(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            f.write_str("\"\"")
+        } else {
+            f.write_str("\"***\"")
+        }
+    }
+}
+
+/// Read a secret file, as mounted by Docker or Kubernetes, without its final newline
+pub fn read_secret_file(path: &str) -> Result<String, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read secret file {}: {}", path, e))?;
+    Ok(contents.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Value of `name` from the environment, or from the file named by `name_FILE`
+fn lookup(name: &str) -> Result<String, String> {
+    if let Ok(value) = env::var(name) {
+        return Ok(value);
+    }
+    match env::var(format!("{}_FILE", name)) {
+        Ok(path) => read_secret_file(&path),
+        Err(_) => Err(format!(
+            "Environment variable {0} is not set, nor is {0}_FILE",
+            name
+        )),
+    }
+}
+
+/// Replace each `${NAME}` in `value` with the variable, or the contents of
+/// the file `NAME_FILE` points to. `$${` escapes a literal `${`.
+pub fn interpolate(value: &str) -> Result<String, String> {
+    let mut resolved = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            resolved.push_str(&rest[..start - 1]);
+            resolved.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        resolved.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| "Unterminated ${ in secret value".to_string())?;
+        resolved.push_str(&lookup(&rest[start + 2..start + end])?);
+        rest = &rest[start + end + 1..];
+    }
+    resolved.push_str(rest);
+    Ok(resolved)
+}
@@ -1,35 +1,125 @@
-use crate::utils::task_manager::models;
+use crate::utils::task_manager::models::{MisfirePolicy, PeriodicTask};
+use crate::utils::task_manager::store::ScheduleStore;
 use crate::utils::task_manager::tasks::{remove_task, upsert_task};
 use crate::utils::text::normalize_cron;
-use chrono::{Local};
+use chrono::{DateTime, Duration, Local, NaiveDateTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
 use cron::Schedule;
 use tracing::debug;
-use redis::AsyncCommands;
-use redis::aio::MultiplexedConnection;
 use std::str::FromStr;
+use thiserror::Error;
 use tracing::info;
 
-pub fn next_run_timestamp(expr: &str) -> i64 {
-    let schedule = Schedule::from_str(expr).unwrap();
-    // schedule.upcoming(Utc).next().unwrap().timestamp()
-    schedule.upcoming(Local).next().unwrap().timestamp()
+#[derive(Debug, Error)]
+pub enum ScheduleError {
+    #[error("invalid cron expression '{0}': {1}")]
+    InvalidCron(String, String),
+    #[error("unknown timezone '{0}'")]
+    UnknownTimezone(String),
+    #[error("cron expression '{0}' has no upcoming run")]
+    NoUpcomingRun(String),
+}
+
+/// Parse an IANA timezone name such as `Europe/Paris`
+pub fn parse_timezone(name: &str) -> Option<Tz> {
+    name.parse::<Tz>().ok()
+}
+
+/// Map a wall-clock time to an instant. A time skipped by a DST gap runs at
+/// the first valid instant after the gap, a repeated time at its earliest instant.
+pub fn resolve<Z: TimeZone>(tz: &Z, wall: NaiveDateTime) -> Option<DateTime<Z>> {
+    if let Some(t) = tz.from_local_datetime(&wall).earliest() {
+        return Some(t);
+    }
+    let mut probe = wall.with_second(0)?;
+    // Gaps are at most a few hours long
+    for _ in 0..24 * 60 {
+        probe += Duration::minutes(1);
+        if let Some(t) = tz.from_local_datetime(&probe).earliest() {
+            return Some(t);
+        }
+    }
+    None
+}
+
+/// Occurrences strictly after `after`, evaluated on the wall clock of `tz`.
+/// Occurrences that resolve to an instant already yielded are dropped, so
+/// each wall-clock time runs at most once across DST transitions.
+fn occurrences_after<'a, Z: TimeZone + 'a>(
+    schedule: &'a Schedule,
+    tz: Z,
+    after: i64,
+) -> impl Iterator<Item = i64> + 'a {
+    let start = DateTime::from_timestamp(after, 0).unwrap_or_default();
+    let wall = Utc.from_utc_datetime(&start.with_timezone(&tz).naive_local());
+    let mut last = after;
+    schedule.after(&wall).filter_map(move |occurrence| {
+        let ts = resolve(&tz, occurrence.naive_utc())?.timestamp();
+        (ts > last).then(|| {
+            last = ts;
+            ts
+        })
+    })
+}
+
+fn upcoming<'a>(
+    schedule: &'a Schedule,
+    timezone: Option<&str>,
+    after: i64,
+) -> Box<dyn Iterator<Item = i64> + 'a> {
+    match timezone.and_then(parse_timezone) {
+        Some(tz) => Box::new(occurrences_after(schedule, tz, after)),
+        None => Box::new(occurrences_after(schedule, Local, after)),
+    }
+}
+
+/// Next run of `expr` in `timezone`, the agent's local time when unset
+pub fn next_run_timestamp(expr: &str, timezone: Option<&str>) -> Result<i64, ScheduleError> {
+    let schedule = Schedule::from_str(expr)
+        .map_err(|e| ScheduleError::InvalidCron(expr.to_string(), e.to_string()))?;
+    if let Some(name) = timezone
+        && parse_timezone(name).is_none()
+    {
+        return Err(ScheduleError::UnknownTimezone(name.to_string()));
+    }
+    upcoming(&schedule, timezone, Utc::now().timestamp())
+        .next()
+        .ok_or_else(|| ScheduleError::NoUpcomingRun(expr.to_string()))
+}
+
+/// Occurrences of `expr` from `from` (included) up to `until` (included)
+pub fn occurrences_between(expr: &str, timezone: Option<&str>, from: i64, until: i64) -> Vec<i64> {
+    let Ok(schedule) = Schedule::from_str(expr) else {
+        return vec![from];
+    };
+    upcoming(&schedule, timezone, from - 1)
+        .take_while(|ts| *ts <= until)
+        .collect()
 }
 
 pub async fn check_and_update_cron(
-    conn: &mut MultiplexedConnection,
+    store: &dyn ScheduleStore,
     cron_value: Option<String>,
     args: Vec<String>,
+    misfire: MisfirePolicy,
+    timezone: Option<String>,
     task: &str,
     task_name: String,
 ) {
-    let redis_key = format!("redbeat:{}", task_name);
-
-    let exists: bool = conn.exists(&redis_key).await.unwrap_or(false);
+    // `None` when the stored task could not be read, the definition is then
+    // written again since rewriting an unchanged task is harmless
+    let stored = match store.get(&task_name).await {
+        Ok(stored) => Some(stored),
+        Err(e) => {
+            tracing::error!("Failed to read task {}, rewriting it: {:?}", task_name, e);
+            None
+        }
+    };
 
     match cron_value {
         None => {
-            if exists {
-                remove_task(conn, &task_name).await.unwrap_or_else(|e| {
+            if !matches!(stored, Some(None)) {
+                remove_task(store, &task_name).await.unwrap_or_else(|e| {
                     tracing::error!("Failed to remove task {}: {:?}", task_name, e);
                 });
                 info!("Task {} removed", task_name);
@@ -37,28 +127,37 @@ pub async fn check_and_update_cron(
         }
 
         Some(cron) => {
-            let cron = normalize_cron(&cron);
-            debug!("Task cron (normalized): {:?}", cron);
-
-            if exists {
-                let raw: String = conn.hget(&redis_key, "data").await.unwrap();
-                let stored: models::PeriodicTask = serde_json::from_str(&raw).unwrap();
+            let entry = PeriodicTask {
+                task: task.to_string(),
+                cron: normalize_cron(&cron),
+                args,
+                enabled: true,
+                misfire,
+                timezone,
+                last_error: None,
+            };
+            debug!("Task cron (normalized): {:?}", entry.cron);
 
-                if stored.cron != cron {
-                    upsert_task(conn, &task_name, task, &cron, args.clone())
-                        .await
-                        .unwrap_or_else(|e| {
-                            tracing::error!("Failed to update task {}: {:?}", task_name, e);
-                        });
-                    info!("Task {} updated", task_name);
+            let changed = match &stored {
+                None | Some(None) => true,
+                Some(Some(stored)) => {
+                    stored.cron != entry.cron
+                        || stored.misfire != entry.misfire
+                        || stored.timezone != entry.timezone
+                        // Quarantined for a reason the new definition no longer has
+                        || (!stored.enabled
+                            && next_run_timestamp(&entry.cron, entry.timezone.as_deref())
+                                .is_ok())
                 }
-            } else {
-                upsert_task(conn, &task_name, task, &cron, args)
-                    .await
-                    .unwrap_or_else(|e| {
-                        tracing::error!("Failed to create task {}: {:?}", task_name, e);
-                    });
-                info!("Task {} created", task_name);
+            };
+            if !changed {
+                return;
+            }
+
+            match upsert_task(store, &task_name, entry).await {
+                Ok(()) if matches!(stored, Some(Some(_))) => info!("Task {} updated", task_name),
+                Ok(()) => info!("Task {} created", task_name),
+                Err(e) => tracing::error!("Task {} quarantined: {:?}", task_name, e),
             }
         }
     }
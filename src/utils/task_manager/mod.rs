@@ -2,3 +2,4 @@ pub mod cron;
 pub mod models;
 pub mod scheduler;
 pub mod tasks;
+pub mod store;
@@ -6,4 +6,25 @@ pub struct PeriodicTask {
     pub cron: String,
     pub args: Vec<String>,
     pub enabled: bool,
+    #[serde(default)]
+    pub misfire: MisfirePolicy,
+    /// IANA timezone the cron expression is evaluated in, the agent's local time when unset
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// Why the task was quarantined, quarantined tasks are disabled and never due
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+/// What the scheduler does with occurrences that came due while the agent was down
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MisfirePolicy {
+    /// Drop the missed occurrences and wait for the next one
+    Skip,
+    /// Run once, however many occurrences were missed
+    #[default]
+    RunOnce,
+    /// Run once if the latest missed occurrence is at most this many seconds old
+    RunIfWithin(u64),
 }
@@ -1,73 +1,191 @@
 use crate::core::context::Context;
 use crate::services::backup::BackupService;
-use crate::services::config::ConfigService;
+use crate::services::config::{BlackoutAction, ConfigService};
+use crate::services::verify::VerifyService;
+use crate::utils::blackout::ActiveBlackout;
 use crate::utils::common::BackupMethod;
-use crate::utils::task_manager::cron::next_run_timestamp;
-use crate::utils::task_manager::models::PeriodicTask;
-use crate::utils::task_manager::tasks::SCHEDULE_KEY;
-use tracing::info;
-use redis::AsyncCommands;
-use redis::aio::MultiplexedConnection;
+use crate::utils::health::{SCHEDULER_TICK, STORE_REACHABLE};
+use crate::utils::metrics;
+use crate::services::cron::{CronService, MissedRun};
+use crate::utils::task_manager::cron::{next_run_timestamp, occurrences_between};
+use crate::utils::task_manager::models::{MisfirePolicy, PeriodicTask};
+use crate::utils::task_manager::store::ScheduleStore;
 use std::sync::Arc;
-use tracing::error;
+use std::sync::atomic::Ordering;
+use tracing::{debug, error, info, warn};
+
+/// Lateness tolerated before an occurrence counts as missed, covers the loop tick
+const MISFIRE_TOLERANCE_SECS: i64 = 60;
+
+pub async fn scheduler_loop(store: Arc<dyn ScheduleStore>) {
+    match store.list().await {
+        Ok(tasks) => info!("Scheduler started with {} persisted tasks", tasks.len()),
+        Err(e) => error!("Failed to list persisted tasks: {:?}", e),
+    }
 
-pub async fn scheduler_loop(mut conn: MultiplexedConnection) {
     loop {
         // let now = chrono::Utc::now().timestamp();
         let now = chrono::Local::now().timestamp();
         // info!("Scheduling task {}", chrono::Local::now());
 
-        let due: Vec<String> = conn
-            .zrangebyscore(SCHEDULE_KEY, 0, now)
-            .await
-            .unwrap_or_default();
+        let due: Vec<(String, i64)> = match store.due(now).await {
+            Ok(due) => {
+                STORE_REACHABLE.store(true, Ordering::SeqCst);
+                due
+            }
+            Err(e) => {
+                error!("Failed to read due tasks: {:?}", e);
+                STORE_REACHABLE.store(false, Ordering::SeqCst);
+                Vec::new()
+            }
+        };
 
-        for key in due {
-            let raw: String = conn.hget(&key, "data").await.unwrap();
-            let task: PeriodicTask = serde_json::from_str(&raw).unwrap();
+        for (name, due_at) in due {
+            let task: PeriodicTask = match store.get(&name).await {
+                Ok(Some(task)) => task,
+                Ok(None) => {
+                    quarantine(store.as_ref(), &name, "scheduled without a task definition").await;
+                    continue;
+                }
+                Err(e) => {
+                    quarantine(store.as_ref(), &name, &e.to_string()).await;
+                    continue;
+                }
+            };
 
             if !task.enabled {
                 continue;
             }
 
-            let task_clone = task.clone();
-            let mut conn_clone = conn.clone();
+            // Move the task to its next run before executing it, whoever
+            // fails the claim lost the race for this occurrence
+            let next_ts = match next_run_timestamp(&task.cron, task.timezone.as_deref()) {
+                Ok(ts) => ts,
+                Err(e) => {
+                    quarantine(store.as_ref(), &name, &e.to_string()).await;
+                    continue;
+                }
+            };
+            match store.claim(&name, due_at, next_ts).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    debug!("Task {} at {} already claimed", name, due_at);
+                    continue;
+                }
+                Err(e) => {
+                    error!("Failed to claim task {}: {:?}", name, e);
+                    continue;
+                }
+            }
+
+            if now - due_at > MISFIRE_TOLERANCE_SECS && !catch_up(&name, &task, due_at, now) {
+                continue;
+            }
+
+            if let Some(blackout) = blackout_for(&task, now) {
+                if blackout.action == BlackoutAction::Defer {
+                    if let Err(e) = store.claim(&name, next_ts, blackout.ends_at).await {
+                        error!("Failed to defer task {}: {:?}", name, e);
+                    }
+                    info!(
+                        "Task {} falls in a blackout window, deferred to {}",
+                        name, blackout.ends_at
+                    );
+                } else {
+                    info!("Task {} falls in a blackout window, skipped", name);
+                }
+                continue;
+            }
 
+            metrics::record_scheduler_lag(&name, now - due_at);
             tokio::spawn(async move {
-                info!(
-                    "Executing task={} args={:?}",
-                    task_clone.task, task_clone.args
-                );
-
-                // let _ = execute_task(task_clone.task.as_str(), task_clone.args).await;
+                info!("Executing task={} args={:?}", task.task, task.args);
 
-                if let Err(e) = execute_task(task_clone.task.as_str(), task_clone.args).await {
+                if let Err(e) = execute_task(task.task.as_str(), task.args).await {
                     error!(
                         "An error occurred while executing task={} : {:?}",
-                        task_clone.task, e
+                        task.task, e
                     );
                 }
-
-                let next_ts = next_run_timestamp(&task_clone.cron);
-                let _: () = conn_clone.zadd(SCHEDULE_KEY, &key, next_ts).await.unwrap();
             });
         }
+        SCHEDULER_TICK.beat();
 
         tokio::time::sleep(std::time::Duration::from_secs(1)).await;
     }
 }
 
+/// Blackout covering the database a task works on
+fn blackout_for(task: &PeriodicTask, now: i64) -> Option<ActiveBlackout> {
+    let generated_id = task.args.first()?;
+    let config = ConfigService::new(Arc::new(Context::new()))
+        .current()
+        .map_err(|e| error!("Failed to load config for blackout check: {}", e))
+        .ok()?;
+    config.blackout(generated_id, now)
+}
+
+/// Take a task the scheduler cannot run out of the schedule, the error is
+/// reported to the server with the next status call
+async fn quarantine(store: &dyn ScheduleStore, name: &str, error: &str) {
+    error!("Quarantining task {}: {}", name, error);
+    if let Err(e) = store.quarantine(name, error).await {
+        error!("Failed to quarantine task {}: {:?}", name, e);
+    }
+}
+
+/// Decide whether a late occurrence still runs under the task's misfire policy,
+/// and report the missed occurrences to the server
+fn catch_up(name: &str, task: &PeriodicTask, due_at: i64, now: i64) -> bool {
+    let missed = occurrences_between(&task.cron, task.timezone.as_deref(), due_at, now);
+    let first = missed.first().copied().unwrap_or(due_at);
+    let last = missed.last().copied().unwrap_or(due_at);
+
+    let run = match task.misfire {
+        MisfirePolicy::Skip => false,
+        MisfirePolicy::RunOnce => true,
+        MisfirePolicy::RunIfWithin(grace) => now - last <= grace as i64,
+    };
+
+    warn!(
+        "Task {} missed {} run(s) since {} ({:?}), {}",
+        name,
+        missed.len(),
+        first,
+        task.misfire,
+        if run { "running once now" } else { "skipping" }
+    );
+
+    let report = MissedRun {
+        name: name.to_string(),
+        task: task.task.clone(),
+        generated_id: task.args.first().cloned(),
+        policy: task.misfire,
+        missed: missed.len().max(1),
+        first_missed_at: first,
+        last_missed_at: last,
+        caught_up: run,
+    };
+    tokio::spawn(async move {
+        let service = CronService::new(Arc::new(Context::new())).await;
+        service.report_missed(&report).await;
+    });
+
+    run
+}
+
 pub async fn execute_task(task: &str, args: Vec<String>) -> Result<(), anyhow::Error> {
     match task {
         "tasks.database.periodic_backup" => {
-            let generated_id = &args[0];
-            let dbms = &args[1];
+            let [generated_id, dbms, ..] = args.as_slice() else {
+                anyhow::bail!("Invalid arguments for {}: {:?}", task, args)
+            };
             info!("{} | {}", generated_id, dbms);
 
             let ctx = Arc::new(Context::new());
             let config_service = ConfigService::new(ctx.clone());
             let backup_service = BackupService::new(ctx.clone());
-            let config = config_service.load(None).unwrap();
+            let config = config_service.current().map_err(anyhow::Error::msg)?;
 
             backup_service
                 .dispatch(generated_id, &config, BackupMethod::Automatic)
@@ -76,6 +194,22 @@ pub async fn execute_task(task: &str, args: Vec<String>) -> Result<(), anyhow::E
             Ok(())
         }
 
+        "tasks.database.periodic_verify" => {
+            let Some(generated_id) = args.first() else {
+                anyhow::bail!("Invalid arguments for {}: {:?}", task, args)
+            };
+            info!("Verification | {}", generated_id);
+
+            let ctx = Arc::new(Context::new());
+            let config_service = ConfigService::new(ctx.clone());
+            let verify_service = VerifyService::new(ctx.clone());
+            let config = config_service.current().map_err(anyhow::Error::msg)?;
+
+            verify_service.dispatch(generated_id, &config).await;
+
+            Ok(())
+        }
+
         _ => {
             anyhow::bail!("Unknown task: {}", task)
         }
@@ -0,0 +1,143 @@
+use super::ScheduleStore;
+use crate::settings::CONFIG;
+use crate::utils::task_manager::models::PeriodicTask;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+use tracing::{error, warn};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    task: PeriodicTask,
+    next_run: Option<i64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Schedule {
+    tasks: BTreeMap<String, Entry>,
+}
+
+/// Schedule kept in memory and written through to a JSON file under `DATA_PATH`.
+/// Claims are only atomic within one process, agents sharing a schedule need Redis.
+pub struct FileStore {
+    path: PathBuf,
+    state: Mutex<Schedule>,
+}
+
+impl FileStore {
+    pub fn default_path() -> PathBuf {
+        Path::new(&CONFIG.data_path).join("schedule.json")
+    }
+
+    pub fn open(path: PathBuf) -> Self {
+        let state = match std::fs::read(&path) {
+            Ok(raw) => serde_json::from_slice(&raw).unwrap_or_else(|e| {
+                error!("Corrupt schedule file {:?}, starting empty: {}", path, e);
+                Schedule::default()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Schedule::default(),
+            Err(e) => {
+                warn!("Failed to read schedule file {:?}: {}", path, e);
+                Schedule::default()
+            }
+        };
+
+        FileStore {
+            path,
+            state: Mutex::new(state),
+        }
+    }
+
+    async fn persist(&self, state: &Schedule) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec_pretty(state)?)
+            .await
+            .with_context(|| format!("Failed to write schedule file {:?}", tmp))?;
+        tokio::fs::rename(&tmp, &self.path).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl ScheduleStore for FileStore {
+    async fn get(&self, name: &str) -> Result<Option<PeriodicTask>> {
+        let state = self.state.lock().await;
+        Ok(state.tasks.get(name).map(|e| e.task.clone()))
+    }
+
+    async fn upsert(&self, name: &str, task: &PeriodicTask, next_run: Option<i64>) -> Result<()> {
+        let mut state = self.state.lock().await;
+        state.tasks.insert(
+            name.to_string(),
+            Entry {
+                task: task.clone(),
+                next_run,
+            },
+        );
+        self.persist(&state).await
+    }
+
+    async fn remove(&self, name: &str) -> Result<()> {
+        let mut state = self.state.lock().await;
+        if state.tasks.remove(name).is_some() {
+            self.persist(&state).await?;
+        }
+        Ok(())
+    }
+
+    async fn due(&self, now: i64) -> Result<Vec<(String, i64)>> {
+        let state = self.state.lock().await;
+        Ok(state
+            .tasks
+            .iter()
+            .filter_map(|(name, e)| match e.next_run {
+                Some(ts) if ts <= now => Some((name.clone(), ts)),
+                _ => None,
+            })
+            .collect())
+    }
+
+    async fn claim(&self, name: &str, due_at: i64, next_run: i64) -> Result<bool> {
+        let mut state = self.state.lock().await;
+        match state.tasks.get_mut(name) {
+            Some(entry) if entry.next_run == Some(due_at) => entry.next_run = Some(next_run),
+            _ => return Ok(false),
+        }
+        self.persist(&state).await?;
+        Ok(true)
+    }
+
+    async fn list(&self) -> Result<Vec<(String, PeriodicTask, Option<i64>)>> {
+        let state = self.state.lock().await;
+        Ok(state
+            .tasks
+            .iter()
+            .map(|(name, e)| (name.clone(), e.task.clone(), e.next_run))
+            .collect())
+    }
+
+    async fn quarantine(&self, name: &str, error: &str) -> Result<()> {
+        let mut state = self.state.lock().await;
+        let Some(entry) = state.tasks.get_mut(name) else {
+            return Ok(());
+        };
+        entry.task.enabled = false;
+        entry.task.last_error = Some(error.to_string());
+        entry.next_run = None;
+        self.persist(&state).await
+    }
+
+    async fn quarantined(&self) -> Result<Vec<(String, String)>> {
+        let state = self.state.lock().await;
+        Ok(state
+            .tasks
+            .iter()
+            .filter_map(|(name, e)| Some((name.clone(), e.task.last_error.clone()?)))
+            .collect())
+    }
+}
@@ -0,0 +1,54 @@
+pub mod file;
+#[cfg(feature = "redis")]
+pub mod redbeat;
+
+use crate::settings::CONFIG;
+use crate::utils::task_manager::models::PeriodicTask;
+use anyhow::Result;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+use tracing::info;
+
+/// Persistent schedule of periodic tasks, keyed by task name
+#[async_trait::async_trait]
+pub trait ScheduleStore: Send + Sync {
+    async fn get(&self, name: &str) -> Result<Option<PeriodicTask>>;
+    /// Store `task`, due at `next_run` or never when `None`
+    async fn upsert(&self, name: &str, task: &PeriodicTask, next_run: Option<i64>) -> Result<()>;
+    async fn remove(&self, name: &str) -> Result<()>;
+    /// Tasks whose next run is at or before `now`, with that run time
+    async fn due(&self, now: i64) -> Result<Vec<(String, i64)>>;
+    /// Atomically move a task from `due_at` to `next_run`. Returns false when
+    /// the occurrence was already claimed, by an earlier tick or another agent
+    /// sharing the store, so that each occurrence runs once.
+    async fn claim(&self, name: &str, due_at: i64, next_run: i64) -> Result<bool>;
+    /// Every task with its next run time, when scheduled
+    async fn list(&self) -> Result<Vec<(String, PeriodicTask, Option<i64>)>>;
+    /// Take a task out of the schedule and record why, works on entries
+    /// that can no longer be decoded
+    async fn quarantine(&self, name: &str, error: &str) -> Result<()>;
+    /// Quarantined task names with their error
+    async fn quarantined(&self) -> Result<Vec<(String, String)>>;
+}
+
+static STORE: OnceCell<Arc<dyn ScheduleStore>> = OnceCell::const_new();
+
+fn open() -> Arc<dyn ScheduleStore> {
+    match CONFIG.schedule_store.as_str() {
+        #[cfg(feature = "redis")]
+        "redis" => {
+            info!("Using Redis schedule store");
+            Arc::new(redbeat::RedisStore::new(&CONFIG.redis_url))
+        }
+        "file" => {
+            info!("Using file schedule store");
+            Arc::new(file::FileStore::open(file::FileStore::default_path()))
+        }
+        other => unreachable!("SCHEDULE_STORE '{}' is rejected by the settings", other),
+    }
+}
+
+/// Shared store selected by `SCHEDULE_STORE`
+pub async fn schedule_store() -> Arc<dyn ScheduleStore> {
+    STORE.get_or_init(|| async { open() }).await.clone()
+}
@@ -0,0 +1,184 @@
+use super::ScheduleStore;
+use crate::utils::redis_client;
+use crate::utils::task_manager::models::PeriodicTask;
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use redis::AsyncCommands;
+use redis::aio::MultiplexedConnection;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+/// RedBeat-compatible layout: one `redbeat:<name>` hash per task, next runs in a sorted set.
+/// Entries whose `data` cannot be decoded are quarantined with an `error` field.
+pub const SCHEDULE_KEY: &str = "redbeat:schedule";
+const KEY_PREFIX: &str = "redbeat:";
+
+/// Compare-and-set on the task score: only the caller that still sees the
+/// occurrence at `ARGV[2]` moves it to `ARGV[3]`
+static CLAIM_SCRIPT: Lazy<redis::Script> = Lazy::new(|| {
+    redis::Script::new(
+        r"
+        local score = redis.call('ZSCORE', KEYS[1], ARGV[1])
+        if score and tonumber(score) == tonumber(ARGV[2]) then
+            redis.call('ZADD', KEYS[1], ARGV[3], ARGV[1])
+            return 1
+        end
+        return 0
+        ",
+    )
+});
+
+fn task_key(name: &str) -> String {
+    format!("{}{}", KEY_PREFIX, name)
+}
+
+pub struct RedisStore {
+    url: String,
+    conn: Mutex<Option<MultiplexedConnection>>,
+}
+
+impl RedisStore {
+    pub fn new(url: &str) -> Self {
+        RedisStore {
+            url: url.to_string(),
+            conn: Mutex::new(None),
+        }
+    }
+
+    /// Connect on first use so that an unavailable Redis fails operations, not startup
+    async fn conn(&self) -> Result<MultiplexedConnection> {
+        let mut conn = self.conn.lock().await;
+        if let Some(c) = conn.as_ref() {
+            return Ok(c.clone());
+        }
+        let c = redis_client::redis_connection(&self.url).await?;
+        *conn = Some(c.clone());
+        Ok(c)
+    }
+}
+
+#[async_trait::async_trait]
+impl ScheduleStore for RedisStore {
+    async fn get(&self, name: &str) -> Result<Option<PeriodicTask>> {
+        let mut conn = self.conn().await?;
+        let raw: Option<String> = conn.hget(task_key(name), "data").await?;
+        match raw {
+            Some(raw) => Ok(Some(serde_json::from_str(&raw)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn upsert(&self, name: &str, task: &PeriodicTask, next_run: Option<i64>) -> Result<()> {
+        let mut conn = self.conn().await?;
+        let key = task_key(name);
+        let payload = serde_json::to_string(task)?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic().hset(&key, "data", payload).hdel(&key, "error");
+        match next_run {
+            Some(ts) => pipe.zadd(SCHEDULE_KEY, &key, ts),
+            None => pipe.zrem(SCHEDULE_KEY, &key),
+        };
+        let _: () = pipe.query_async(&mut conn).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, name: &str) -> Result<()> {
+        let mut conn = self.conn().await?;
+        let key = task_key(name);
+
+        let mut pipe = redis::pipe();
+        pipe.atomic().zrem(SCHEDULE_KEY, &key).del(&key);
+        let _: () = pipe.query_async(&mut conn).await?;
+        Ok(())
+    }
+
+    async fn due(&self, now: i64) -> Result<Vec<(String, i64)>> {
+        let mut conn = self.conn().await?;
+        let keys: Vec<(String, i64)> = conn.zrangebyscore_withscores(SCHEDULE_KEY, 0, now).await?;
+        Ok(keys
+            .into_iter()
+            .map(|(k, ts)| (k.trim_start_matches(KEY_PREFIX).to_string(), ts))
+            .collect())
+    }
+
+    async fn claim(&self, name: &str, due_at: i64, next_run: i64) -> Result<bool> {
+        let mut conn = self.conn().await?;
+        let claimed: i64 = CLAIM_SCRIPT
+            .key(SCHEDULE_KEY)
+            .arg(task_key(name))
+            .arg(due_at)
+            .arg(next_run)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(claimed == 1)
+    }
+
+    async fn list(&self) -> Result<Vec<(String, PeriodicTask, Option<i64>)>> {
+        let mut conn = self.conn().await?;
+        let keys: Vec<String> = conn.keys(format!("{}*", KEY_PREFIX)).await?;
+
+        let mut tasks = Vec::new();
+        for key in keys {
+            if key == SCHEDULE_KEY {
+                continue;
+            }
+            let raw: Option<String> = conn.hget(&key, "data").await?;
+            let Some(raw) = raw else { continue };
+            let task: PeriodicTask = match serde_json::from_str(&raw) {
+                Ok(task) => task,
+                Err(e) => {
+                    warn!("Skipping unreadable task {}: {}", key, e);
+                    continue;
+                }
+            };
+            let next_run: Option<i64> = conn.zscore(SCHEDULE_KEY, &key).await?;
+            tasks.push((key.trim_start_matches(KEY_PREFIX).to_string(), task, next_run));
+        }
+        Ok(tasks)
+    }
+
+    async fn quarantine(&self, name: &str, error: &str) -> Result<()> {
+        let mut conn = self.conn().await?;
+        let key = task_key(name);
+        let raw: Option<String> = conn.hget(&key, "data").await?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic().zrem(SCHEDULE_KEY, &key);
+        match raw.and_then(|raw| serde_json::from_str::<PeriodicTask>(&raw).ok()) {
+            Some(mut task) => {
+                task.enabled = false;
+                task.last_error = Some(error.to_string());
+                pipe.hset(&key, "data", serde_json::to_string(&task)?)
+            }
+            None => pipe.hset(&key, "error", error),
+        };
+        let _: () = pipe.query_async(&mut conn).await?;
+        Ok(())
+    }
+
+    async fn quarantined(&self) -> Result<Vec<(String, String)>> {
+        let mut conn = self.conn().await?;
+        let keys: Vec<String> = conn.keys(format!("{}*", KEY_PREFIX)).await?;
+
+        let mut errors = Vec::new();
+        for key in keys {
+            if key == SCHEDULE_KEY {
+                continue;
+            }
+            let (raw, error): (Option<String>, Option<String>) =
+                conn.hmget(&key, &["data", "error"]).await?;
+            let task = raw.and_then(|raw| serde_json::from_str::<PeriodicTask>(&raw).ok());
+            let error = match (error, task) {
+                (Some(error), _) => error,
+                (None, Some(task)) => match task.last_error {
+                    Some(error) => error,
+                    None => continue,
+                },
+                (None, None) => "unreadable task definition".to_string(),
+            };
+            errors.push((key.trim_start_matches(KEY_PREFIX).to_string(), error));
+        }
+        Ok(errors)
+    }
+}
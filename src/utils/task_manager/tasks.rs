@@ -2,42 +2,30 @@
 
 use crate::utils::task_manager::cron::next_run_timestamp;
 use crate::utils::task_manager::models::PeriodicTask;
-use redis::aio::MultiplexedConnection;
-
-pub const SCHEDULE_KEY: &str = "redbeat:schedule";
+use crate::utils::task_manager::store::ScheduleStore;
 
+/// Schedule `task` at its next run. A task whose schedule cannot be evaluated
+/// is stored quarantined instead and the error is returned.
 pub async fn upsert_task(
-    conn: &mut MultiplexedConnection,
+    store: &dyn ScheduleStore,
     name: &str,
-    task: &str,
-    cron: &str,
-    args: Vec<String>,
-) -> redis::RedisResult<()> {
-    let key = format!("redbeat:{}", name);
-    let next_ts = next_run_timestamp(cron);
-
-    let entry = PeriodicTask {
-        task: task.to_string(),
-        cron: cron.to_string(),
-        args,
-        enabled: true,
-    };
-
-    let payload = serde_json::to_string(&entry).unwrap();
-
-    let mut pipe = redis::pipe();
-    pipe.atomic()
-        .hset(&key, "data", payload)
-        .zadd(SCHEDULE_KEY, &key, next_ts);
-
-    pipe.query_async(conn).await
+    mut task: PeriodicTask,
+) -> anyhow::Result<()> {
+    match next_run_timestamp(&task.cron, task.timezone.as_deref()) {
+        Ok(next_ts) => {
+            task.enabled = true;
+            task.last_error = None;
+            store.upsert(name, &task, Some(next_ts)).await
+        }
+        Err(e) => {
+            task.enabled = false;
+            task.last_error = Some(e.to_string());
+            store.upsert(name, &task, None).await?;
+            Err(e.into())
+        }
+    }
 }
 
-pub async fn remove_task(conn: &mut MultiplexedConnection, name: &str) -> redis::RedisResult<()> {
-    let key = format!("redbeat:{}", name);
-
-    let mut pipe = redis::pipe();
-    pipe.atomic().zrem(SCHEDULE_KEY, &key).del(&key);
-
-    pipe.query_async(conn).await
+pub async fn remove_task(store: &dyn ScheduleStore, name: &str) -> anyhow::Result<()> {
+    store.remove(name).await
 }
@@ -4,4 +4,68 @@ pub fn normalize_cron(expr: &str) -> String {
     } else {
         expr.to_string()
     }
-}
\ No newline at end of file
+}
+/// Match `name` against a glob `pattern`, where `*` is any run of characters
+/// and `?` any single one
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*` and the name position it is retried from
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, n));
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bn)) => {
+                    p = bp;
+                    n = bn + 1;
+                    backtrack = Some((bp, bn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn star_matches_any_run() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("app_*", "app_"));
+        assert!(glob_match("app_*", "app_prod"));
+        assert!(glob_match("*_prod", "app_prod"));
+        assert!(glob_match("a*b*c", "aXXbYYc"));
+        assert!(!glob_match("app_*", "ap_prod"));
+        assert!(!glob_match("a*b*c", "aXXbYY"));
+    }
+
+    #[test]
+    fn star_backtracks() {
+        assert!(glob_match("*ab", "aab"));
+        assert!(glob_match("*a*b", "xaxxab"));
+        assert!(!glob_match("*ab", "aba"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_char() {
+        assert!(glob_match("db?", "db1"));
+        assert!(glob_match("d?_*", "db_test"));
+        assert!(glob_match("caf?", "café"));
+        assert!(!glob_match("db?", "db"));
+        assert!(!glob_match("db?", "db12"));
+    }
+}